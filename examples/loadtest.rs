@@ -0,0 +1,207 @@
+//! Drive a running server with a mix of queries and report latencies.
+//!
+//! ```sh
+//! cargo run --release --example loadtest -- \
+//!     --url http://localhost:8000/ --concurrency 16 --requests 500 --mix front,thread
+//! ```
+//!
+//! Every request asks for loader statistics, so the report also shows how
+//! many upstream calls each query class costs on average.
+
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The query classes the harness knows how to send.
+static QUERIES: &[(&str, &str)] = &[
+    ("front", "{ top(first: 30) { nodes { id title author } } }"),
+    (
+        "thread",
+        "{ top(first: 5) { nodes { ... on Story { kidsConnection(first: 20) { nodes { id author } } } } } }",
+    ),
+    (
+        "answers",
+        "{ top(first: 3) { nodes { ... on Story { topAnswers(limit: 5) { replies authorKarma } } } } }",
+    ),
+    ("best-comments", "{ bestComments(limit: 10) { id author } }"),
+];
+
+struct Options {
+    url: String,
+    concurrency: usize,
+    requests: usize,
+    mix: Vec<(&'static str, &'static str)>,
+}
+
+impl Options {
+    fn from_args() -> Result<Self, String> {
+        let mut options = Options {
+            url: "http://localhost:8000/".to_string(),
+            concurrency: 8,
+            requests: 200,
+            mix: QUERIES.to_vec(),
+        };
+
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for {}", arg))?;
+            match arg.as_str() {
+                "--url" => options.url = value,
+                "--concurrency" => options.concurrency = parse_count(&arg, &value)?,
+                "--requests" => options.requests = parse_count(&arg, &value)?,
+                "--mix" => {
+                    options.mix = value
+                        .split(',')
+                        .map(|name| {
+                            QUERIES
+                                .iter()
+                                .find(|(known, _)| *known == name)
+                                .copied()
+                                .ok_or_else(|| format!("unknown query class {}", name))
+                        })
+                        .collect::<Result<_, _>>()?;
+                }
+                _ => return Err(format!("unknown argument {}", arg)),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+fn parse_count(arg: &str, value: &str) -> Result<usize, String> {
+    match value.parse() {
+        Ok(count) if count > 0 => Ok(count),
+        _ => Err(format!("{} expects a positive number", arg)),
+    }
+}
+
+/// The outcome of a single request.
+struct Sample {
+    class: &'static str,
+    latency: Duration,
+    upstream_calls: Option<u64>,
+    ok: bool,
+}
+
+async fn send(client: &reqwest::Client, url: &str, class: &'static str, query: &str) -> Sample {
+    let start = Instant::now();
+    let response = client
+        .post(url)
+        .header("x-loader-stats", "1")
+        .json(&json!({ "query": query }))
+        .send()
+        .await;
+    let body = match response {
+        Ok(response) => response.json::<Value>().await.ok(),
+        Err(_) => None,
+    };
+    let latency = start.elapsed();
+
+    Sample {
+        class,
+        latency,
+        upstream_calls: body
+            .as_ref()
+            .and_then(|body| body["extensions"]["loaderStats"]["upstreamCalls"].as_u64()),
+        ok: body.is_some_and(|body| body.get("errors").is_none()),
+    }
+}
+
+fn percentile(sorted: &[Duration], p: f64) -> Duration {
+    let index = ((sorted.len() as f64 - 1.0) * p).round() as usize;
+    sorted[index]
+}
+
+fn report(samples: &[Sample], elapsed: Duration) {
+    let mut classes: BTreeMap<&str, Vec<&Sample>> = BTreeMap::new();
+    for sample in samples {
+        classes.entry(sample.class).or_default().push(sample);
+    }
+
+    println!(
+        "{} requests in {:.2?} ({:.1} req/s)\n",
+        samples.len(),
+        elapsed,
+        samples.len() as f64 / elapsed.as_secs_f64()
+    );
+    println!(
+        "{:<14} {:>6} {:>6} {:>10} {:>10} {:>10} {:>10} {:>10}",
+        "class", "count", "errors", "p50", "p90", "p99", "max", "upstream"
+    );
+    for (class, samples) in classes {
+        let mut latencies = samples.iter().map(|s| s.latency).collect::<Vec<_>>();
+        latencies.sort();
+        let errors = samples.iter().filter(|s| !s.ok).count();
+        let calls = samples
+            .iter()
+            .filter_map(|s| s.upstream_calls)
+            .collect::<Vec<_>>();
+        let amplification = if calls.is_empty() {
+            "-".to_string()
+        } else {
+            format!(
+                "{:.1}",
+                calls.iter().sum::<u64>() as f64 / calls.len() as f64
+            )
+        };
+
+        println!(
+            "{:<14} {:>6} {:>6} {:>10.2?} {:>10.2?} {:>10.2?} {:>10.2?} {:>10}",
+            class,
+            samples.len(),
+            errors,
+            percentile(&latencies, 0.5),
+            percentile(&latencies, 0.9),
+            percentile(&latencies, 0.99),
+            latencies[latencies.len() - 1],
+            amplification,
+        );
+    }
+    println!("\nupstream: mean upstream calls per request");
+}
+
+#[tokio::main]
+async fn main() {
+    let options = match Options::from_args() {
+        Ok(options) => Arc::new(options),
+        Err(err) => {
+            eprintln!("loadtest: {}", err);
+            std::process::exit(2);
+        }
+    };
+
+    let client = reqwest::Client::new();
+    let next = Arc::new(AtomicUsize::new(0));
+    let start = Instant::now();
+
+    let workers = (0..options.concurrency)
+        .map(|_| {
+            let client = client.clone();
+            let options = options.clone();
+            let next = next.clone();
+            tokio::spawn(async move {
+                let mut samples = Vec::new();
+                loop {
+                    let n = next.fetch_add(1, Ordering::Relaxed);
+                    if n >= options.requests {
+                        break samples;
+                    }
+                    let (class, query) = options.mix[n % options.mix.len()];
+                    samples.push(send(&client, &options.url, class, query).await);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let mut samples = Vec::with_capacity(options.requests);
+    for worker in workers {
+        samples.extend(worker.await.unwrap());
+    }
+
+    report(&samples, start.elapsed());
+}
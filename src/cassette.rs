@@ -0,0 +1,121 @@
+//! Recording and replaying of upstream responses.
+//!
+//! A cassette maps each API path (for example `item/8863.json`) to a file
+//! of the same name below its directory. In record mode the client stores
+//! every response it receives; in replay mode it never touches the network
+//! and serves the stored files instead.
+
+use crate::result::{Error, Result};
+use std::path::{Component, Path, PathBuf};
+
+/// Whether a cassette captures responses or serves them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Fetch from upstream and store every response.
+    Record,
+    /// Serve stored responses without touching the network.
+    Replay,
+}
+
+/// A directory of recorded upstream responses.
+#[derive(Debug, Clone)]
+pub struct Cassette {
+    dir: PathBuf,
+    mode: CassetteMode,
+}
+
+impl Cassette {
+    /// Create a cassette that records responses into `dir`.
+    pub fn record(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            mode: CassetteMode::Record,
+        }
+    }
+
+    /// Create a cassette that replays the responses stored in `dir`.
+    pub fn replay(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            mode: CassetteMode::Replay,
+        }
+    }
+
+    /// Create a cassette from the `HN_CASSETTE_RECORD` or
+    /// `HN_CASSETTE_REPLAY` environment variables, which name its directory.
+    pub fn from_env() -> Option<Self> {
+        if let Some(dir) = std::env::var_os("HN_CASSETTE_RECORD") {
+            return Some(Self::record(dir));
+        }
+        std::env::var_os("HN_CASSETTE_REPLAY").map(Self::replay)
+    }
+
+    /// Return the cassette's mode.
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    /// Return the directory the cassette's responses live in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Read the stored response for `path`.
+    pub(crate) fn load(&self, path: &str) -> Result<Vec<u8>> {
+        Ok(std::fs::read(self.file(path)?)?)
+    }
+
+    /// Store `body` as the response for `path`.
+    pub(crate) fn store(&self, path: &str, body: &[u8]) -> Result<()> {
+        let file = self.file(path)?;
+        if let Some(parent) = file.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Ok(std::fs::write(file, body)?)
+    }
+
+    /// Return the file holding the response for `path`, refusing paths
+    /// that would reach outside the cassette's directory.
+    fn file(&self, path: &str) -> Result<PathBuf> {
+        let relative = Path::new(path);
+        let inside = relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        if !inside || path.is_empty() {
+            return Err(Error::InvalidCassettePath(path.to_string()));
+        }
+        Ok(self.dir.join(relative))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixtures() -> Cassette {
+        Cassette::replay(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/item"))
+    }
+
+    #[test]
+    fn loads_paths_below_the_directory() {
+        assert!(fixtures().load("8863.json").is_ok());
+    }
+
+    #[test]
+    fn refuses_paths_outside_the_directory() {
+        let cassette = fixtures();
+        // The file exists, but only next to the cassette's directory.
+        assert!(matches!(
+            cassette.load("../user/jl.json"),
+            Err(Error::InvalidCassettePath(_))
+        ));
+        assert!(matches!(
+            cassette.load("/etc/hostname"),
+            Err(Error::InvalidCassettePath(_))
+        ));
+        assert!(matches!(
+            cassette.store("user/../../escaped.json", b"{}"),
+            Err(Error::InvalidCassettePath(_))
+        ));
+    }
+}
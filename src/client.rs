@@ -1,168 +1,381 @@
 use std::time::Duration;
 
+#[cfg(feature = "cassette")]
+use crate::cassette::{Cassette, CassetteMode};
+#[cfg(feature = "fault-injection")]
+use crate::faults::{Fault, FaultInjector, MALFORMED_BODY};
 use crate::result::Result;
+use crate::stats::{CountingCache, LoaderStats, UpstreamBudget};
 use crate::types;
-use async_graphql::dataloader::Loader;
+use async_graphql::dataloader::{DataLoader, Loader};
 use futures::future::{join_all, FutureExt};
-use reqwest::{self, Client};
+use reqwest::{self, Client, Url};
+use serde::{de::DeserializeOwned, Deserialize};
 use std::collections::HashMap;
+use std::sync::Arc;
 
 static API_BASE_URL: &str = "https://hacker-news.firebaseio.com/v0";
 
+static SEARCH_BASE_URL: &str = "https://hn.algolia.com/api/v1";
+
+/// Return the `User-Agent` identifying this server to the HN API.
+///
+/// It names the crate and its version, followed by `contact` (a URL or
+/// email address), or the project's repository if none is given.
+pub fn user_agent(contact: Option<&str>) -> String {
+    format!(
+        "{}/{} (+{})",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        contact.unwrap_or(env!("CARGO_PKG_REPOSITORY"))
+    )
+}
+
 /// The API client.
 #[derive(Clone)]
 pub struct HnClient {
     client: Client,
+    stats: Option<Arc<LoaderStats>>,
+    budget: Option<Arc<UpstreamBudget>>,
+    #[cfg(feature = "cassette")]
+    cassette: Option<Arc<Cassette>>,
+    #[cfg(feature = "fault-injection")]
+    faults: Option<Arc<FaultInjector>>,
 }
 
 impl HnClient {
     /// Create a new `HnClient` instance.
     pub fn init() -> Result<Self> {
+        Self::init_with_user_agent(&user_agent(None))
+    }
+
+    /// Create a new `HnClient` instance sending `user_agent` upstream.
+    pub fn init_with_user_agent(user_agent: &str) -> Result<Self> {
         let client = reqwest::Client::builder()
             .timeout(Duration::from_secs(10))
+            .user_agent(user_agent)
             .build()?;
-        Ok(Self { client })
+        Ok(Self {
+            client,
+            stats: None,
+            budget: None,
+            #[cfg(feature = "cassette")]
+            cassette: None,
+            #[cfg(feature = "fault-injection")]
+            faults: None,
+        })
+    }
+
+    /// Return a copy of this client that records its upstream calls in `stats`.
+    pub fn with_stats(&self, stats: Arc<LoaderStats>) -> Self {
+        Self {
+            stats: Some(stats),
+            ..self.clone()
+        }
+    }
+
+    /// Return a copy of this client that records responses to, or replays
+    /// them from, `cassette`.
+    #[cfg(feature = "cassette")]
+    pub fn with_cassette(&self, cassette: Cassette) -> Self {
+        Self {
+            cassette: Some(Arc::new(cassette)),
+            ..self.clone()
+        }
+    }
+
+    /// Return a copy of this client that injects faults into its upstream
+    /// calls with `faults`.
+    #[cfg(feature = "fault-injection")]
+    pub fn with_faults(&self, faults: FaultInjector) -> Self {
+        Self {
+            faults: Some(Arc::new(faults)),
+            ..self.clone()
+        }
+    }
+
+    /// Return a copy of this client whose upstream calls are limited by
+    /// `budget`.
+    pub fn with_budget(&self, budget: Arc<UpstreamBudget>) -> Self {
+        Self {
+            budget: Some(budget),
+            ..self.clone()
+        }
+    }
+
+    /// Return the statistics this client records into, if any.
+    pub fn stats(&self) -> Option<&LoaderStats> {
+        self.stats.as_deref()
+    }
+
+    /// Fetch and decode the JSON document at `path`, relative to the API base.
+    async fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        self.fetch_json(&format!("{}/{}", API_BASE_URL, path), path)
+            .await
+    }
+
+    /// Fetch and decode the JSON document at `url`, recorded in cassettes
+    /// under `path`.
+    #[cfg_attr(not(feature = "cassette"), allow(unused_variables))]
+    async fn fetch_json<T: DeserializeOwned>(&self, url: &str, path: &str) -> Result<T> {
+        #[cfg(feature = "fault-injection")]
+        if let Some(faults) = &self.faults {
+            if let Some(Fault::Malformed) = faults.inject().await? {
+                return Ok(serde_json::from_slice(MALFORMED_BODY)?);
+            }
+        }
+
+        // Replayed calls count too, so budgets behave the same against a
+        // cassette as against the live API.
+        if let Some(budget) = &self.budget {
+            budget.acquire()?;
+        }
+        if let Some(stats) = self.stats() {
+            stats.record_upstream_call();
+        }
+
+        #[cfg(feature = "cassette")]
+        if let Some(cassette) = &self.cassette {
+            if cassette.mode() == CassetteMode::Replay {
+                return Ok(serde_json::from_slice(&cassette.load(path)?)?);
+            }
+        }
+
+        let body = self.client.get(url).send().await?.bytes().await?;
+
+        #[cfg(feature = "cassette")]
+        if let Some(cassette) = &self.cassette {
+            cassette.store(path, &body)?;
+        }
+
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// Return the raw JSON document at `path`, relative to the API base.
+    pub async fn get_raw(&self, path: &str) -> Result<serde_json::Value> {
+        self.get_json(path).await
     }
 
     /// Return the item with the specified id.
     ///
     /// May return `None` if item id is invalid.
-    pub async fn get_item(&self, id: u32) -> Result<Option<types::Item>> {
-        Ok(self
-            .client
-            .get(&format!("{}/item/{}.json", API_BASE_URL, id))
-            .send()
-            .await?
-            .json()
-            .await?)
+    pub async fn get_item(&self, id: types::ItemId) -> Result<Option<types::Item>> {
+        let mut item: Option<types::Item> = self.get_json(&format!("item/{}.json", id)).await?;
+        if let Some(item) = &mut item {
+            item.set_fetched_at(types::now());
+        }
+        Ok(item)
     }
 
     /// Return the user with the specified username.
     ///
     /// May return `None` if username is invalid.
-    pub async fn get_user(&self, username: &str) -> Result<Option<types::User>> {
-        Ok(self
-            .client
-            .get(&format!("{}/user/{}.json", API_BASE_URL, username))
-            .send()
-            .await?
-            .json()
-            .await?)
+    pub async fn get_user(&self, username: &types::Username) -> Result<Option<types::User>> {
+        self.get_json(&format!("user/{}.json", username)).await
     }
 
     /// Return the id of the newest item.
     ///
     /// To get the 10 latest items, you can decrement the id 10 times.
-    pub async fn get_max_item_id(&self) -> Result<u32> {
-        Ok(self
-            .client
-            .get(&format!("{}/maxitem.json", API_BASE_URL))
-            .send()
-            .await?
-            .json()
-            .await?)
+    pub async fn get_max_item_id(&self) -> Result<types::ItemId> {
+        self.get_json("maxitem.json").await
     }
 
     /// Return a list of top story item ids.
-    pub async fn get_top_stories(&self) -> Result<Vec<u32>> {
-        Ok(self
-            .client
-            .get(&format!("{}/topstories.json", API_BASE_URL))
-            .send()
-            .await?
-            .json()
-            .await?)
+    pub async fn get_top_stories(&self) -> Result<Vec<types::ItemId>> {
+        self.get_json("topstories.json").await
     }
 
     /// Return a list of new story item ids.
-    pub async fn get_new_stories(&self) -> Result<Vec<u32>> {
-        Ok(self
-            .client
-            .get(&format!("{}/newstories.json", API_BASE_URL))
-            .send()
-            .await?
-            .json()
-            .await?)
+    pub async fn get_new_stories(&self) -> Result<Vec<types::ItemId>> {
+        self.get_json("newstories.json").await
     }
 
     /// Return a list of best story item ids.
-    pub async fn get_best_stories(&self) -> Result<Vec<u32>> {
-        Ok(self
-            .client
-            .get(&format!("{}/beststories.json", API_BASE_URL))
-            .send()
-            .await?
-            .json()
-            .await?)
+    pub async fn get_best_stories(&self) -> Result<Vec<types::ItemId>> {
+        self.get_json("beststories.json").await
     }
 
     /// Return up to 200 latest Ask HN story item ids.
-    pub async fn get_ask_stories(&self) -> Result<Vec<u32>> {
-        Ok(self
-            .client
-            .get(&format!("{}/askstories.json", API_BASE_URL))
-            .send()
-            .await?
-            .json()
-            .await?)
+    pub async fn get_ask_stories(&self) -> Result<Vec<types::ItemId>> {
+        self.get_json("askstories.json").await
     }
 
     /// Return up to 200 latest Show HN story item ids.
-    pub async fn get_show_stories(&self) -> Result<Vec<u32>> {
-        Ok(self
-            .client
-            .get(&format!("{}/showstories.json", API_BASE_URL))
-            .send()
-            .await?
-            .json()
-            .await?)
+    pub async fn get_show_stories(&self) -> Result<Vec<types::ItemId>> {
+        self.get_json("showstories.json").await
     }
 
     /// Return up to 200 latest Job story item ids.
-    pub async fn get_job_stories(&self) -> Result<Vec<u32>> {
-        Ok(self
-            .client
-            .get(&format!("{}/jobstories.json", API_BASE_URL))
-            .send()
-            .await?
-            .json()
-            .await?)
+    pub async fn get_job_stories(&self) -> Result<Vec<types::ItemId>> {
+        self.get_json("jobstories.json").await
+    }
+
+    /// Return the item ids in the given story list.
+    pub async fn get_stories(&self, list: types::StoryList) -> Result<Vec<types::ItemId>> {
+        match list {
+            types::StoryList::Top => self.get_top_stories().await,
+            types::StoryList::New => self.get_new_stories().await,
+            types::StoryList::Best => self.get_best_stories().await,
+            types::StoryList::Ask => self.get_ask_stories().await,
+            types::StoryList::Show => self.get_show_stories().await,
+            types::StoryList::Job => self.get_job_stories().await,
+        }
     }
 
     /// Return a list of items and users that have been updated recently.
     pub async fn get_updates(&self) -> Result<types::Updates> {
-        Ok(self
-            .client
-            .get(&format!("{}/updates.json", API_BASE_URL))
-            .send()
-            .await?
-            .json()
-            .await?)
+        self.get_json("updates.json").await
     }
+
+    /// Return the ids of up to `limit` of the most recently submitted
+    /// polls, newest first.
+    ///
+    /// The Firebase API has no poll list, so this asks HN Search (Algolia).
+    pub async fn get_recent_poll_ids(&self, limit: usize) -> Result<Vec<types::ItemId>> {
+        let results: SearchResults = self
+            .fetch_json(
+                &format!(
+                    "{}/search_by_date?tags=poll&hitsPerPage={}",
+                    SEARCH_BASE_URL, limit
+                ),
+                &format!("search/polls-{}.json", limit),
+            )
+            .await?;
+
+        Ok(results
+            .hits
+            .into_iter()
+            .filter_map(|hit| hit.object_id.parse().ok().map(types::ItemId))
+            .collect())
+    }
+
+    /// Return the ids of up to `limit` stories whose URL matches `url`,
+    /// most relevant first.
+    ///
+    /// Matching is done by HN Search and is fuzzy; callers should compare
+    /// the loaded stories' URLs themselves.
+    pub async fn search_story_ids_by_url(
+        &self,
+        url: &str,
+        limit: usize,
+    ) -> Result<Vec<types::ItemId>> {
+        let search_url = Url::parse_with_params(
+            &format!("{}/search", SEARCH_BASE_URL),
+            &[
+                ("query", url),
+                ("restrictSearchableAttributes", "url"),
+                ("tags", "story"),
+                ("hitsPerPage", &limit.to_string()),
+            ],
+        )
+        .expect("the search base URL is valid");
+        let key = url
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect::<String>();
+        let results: SearchResults = self
+            .fetch_json(
+                search_url.as_str(),
+                &format!("search/url-{}-{}.json", key, limit),
+            )
+            .await?;
+
+        Ok(results
+            .hits
+            .into_iter()
+            .filter_map(|hit| hit.object_id.parse().ok().map(types::ItemId))
+            .collect())
+    }
+}
+
+/// A page of HN Search results.
+#[derive(Deserialize)]
+struct SearchResults {
+    hits: Vec<SearchHit>,
+}
+
+/// A single HN Search result. Its object id is the item id.
+#[derive(Deserialize)]
+struct SearchHit {
+    #[serde(rename = "objectID")]
+    object_id: String,
 }
 
+/// The item loader as registered with the schema and with requests.
+pub type ItemDataLoader = DataLoader<ItemLoader, CountingCache>;
+
+/// The user loader as registered with the schema and with requests.
+pub type UserDataLoader = DataLoader<UserLoader, CountingCache>;
+
+/// A dataloader batching and deduplicating item lookups.
 pub struct ItemLoader {
     pub client: HnClient,
 }
 
 #[async_trait::async_trait]
-impl Loader<u32> for ItemLoader {
+impl Loader<types::ItemId> for ItemLoader {
     type Value = types::Item;
     type Error = ();
 
-    async fn load(&self, keys: &[u32]) -> std::result::Result<HashMap<u32, Self::Value>, ()> {
+    async fn load(
+        &self,
+        keys: &[types::ItemId],
+    ) -> std::result::Result<HashMap<types::ItemId, Self::Value>, ()> {
         let results = keys
-            .into_iter()
+            .iter()
             .map(|id| self.client.get_item(*id).map(move |res| (*id, res)))
             .collect::<Vec<_>>();
 
-        Ok(join_all(results)
+        let items: HashMap<_, _> = join_all(results)
             .await
             .into_iter()
             .filter_map(|(id, res)| match res {
                 Ok(Some(val)) => Some((id, val)),
                 _ => None,
             })
-            .collect())
+            .collect();
+
+        if let Some(stats) = self.client.stats() {
+            stats.record_batch(items.len());
+        }
+
+        Ok(items)
+    }
+}
+
+/// A dataloader batching and deduplicating user lookups.
+pub struct UserLoader {
+    pub client: HnClient,
+}
+
+#[async_trait::async_trait]
+impl Loader<types::Username> for UserLoader {
+    type Value = types::User;
+    type Error = ();
+
+    async fn load(
+        &self,
+        keys: &[types::Username],
+    ) -> std::result::Result<HashMap<types::Username, Self::Value>, ()> {
+        let results = keys
+            .iter()
+            .map(|id| self.client.get_user(id).map(move |res| (id.clone(), res)))
+            .collect::<Vec<_>>();
+
+        let users: HashMap<_, _> = join_all(results)
+            .await
+            .into_iter()
+            .filter_map(|(id, res)| match res {
+                Ok(Some(val)) => Some((id, val)),
+                _ => None,
+            })
+            .collect();
+
+        if let Some(stats) = self.client.stats() {
+            stats.record_batch(users.len());
+        }
+
+        Ok(users)
     }
 }
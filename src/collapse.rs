@@ -0,0 +1,158 @@
+//! Heuristics deciding which comments readers should show collapsed.
+//!
+//! Clients rendering threads get the same default from every server
+//! instead of each implementing their own rules.
+
+use crate::textproc::{PlainText, Processor};
+use crate::types::{load_items, Comment, Item};
+use async_graphql::Context;
+
+/// The default depth from which comments are collapsed.
+pub const DEFAULT_COLLAPSE_DEPTH: usize = 8;
+
+/// The default length below which a reply without replies of its own is
+/// considered low-signal.
+pub const DEFAULT_SHORT_REPLY_CHARS: usize = 12;
+
+/// Thresholds for collapsing comments, registered as schema data.
+///
+/// Schemas without registered rules use the defaults.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct CollapseRules {
+    /// Comments at least this deep are collapsed, when their depth is
+    /// known. `None` disables the rule.
+    pub depth: Option<usize>,
+    /// Comments without replies whose plain text is shorter than this many
+    /// characters, such as "+1" or "Thanks!", are collapsed. Zero disables
+    /// the rule.
+    pub short_reply_chars: usize,
+}
+
+impl Default for CollapseRules {
+    fn default() -> Self {
+        Self {
+            depth: Some(DEFAULT_COLLAPSE_DEPTH),
+            short_reply_chars: DEFAULT_SHORT_REPLY_CHARS,
+        }
+    }
+}
+
+impl CollapseRules {
+    /// Read the rules from `HN_COLLAPSE_DEPTH` and
+    /// `HN_COLLAPSE_SHORT_REPLY_CHARS`, falling back to the defaults for
+    /// unset or invalid values. A depth of 0 disables the depth rule.
+    pub fn from_env() -> Self {
+        fn var(name: &str) -> Option<usize> {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+        }
+        let defaults = Self::default();
+        Self {
+            depth: var("HN_COLLAPSE_DEPTH")
+                .map(|depth| Some(depth).filter(|&depth| depth > 0))
+                .unwrap_or(defaults.depth),
+            short_reply_chars: var("HN_COLLAPSE_SHORT_REPLY_CHARS")
+                .unwrap_or(defaults.short_reply_chars),
+        }
+    }
+
+    /// Return the rules registered with the schema.
+    pub(crate) fn get(ctx: &Context<'_>) -> Self {
+        ctx.data_opt::<Self>().copied().unwrap_or_default()
+    }
+
+    /// Return whether `comment` should be collapsed by default.
+    ///
+    /// Besides the thresholds, comments that are deleted or dead, or whose
+    /// parent comment is, are always collapsed.
+    pub(crate) async fn collapses(&self, ctx: &Context<'_>, comment: &Comment) -> bool {
+        if self.collapses_alone(comment) {
+            return true;
+        }
+
+        // A parent that fails to load is not held against its replies.
+        match load_items(ctx, &[comment.parent]).await.pop() {
+            Some(Item::Comment(parent)) => parent.deleted || parent.dead,
+            _ => false,
+        }
+    }
+
+    /// Return whether `comment` collapses whatever its parent.
+    fn collapses_alone(&self, comment: &Comment) -> bool {
+        if comment.deleted || comment.dead {
+            return true;
+        }
+        if let (Some(max), Some(depth)) = (self.depth, comment.depth) {
+            if depth >= max {
+                return true;
+            }
+        }
+        comment.kids.is_empty()
+            && PlainText.process(&comment.text).trim().chars().count() < self.short_reply_chars
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn comment(text: &str, kids: &[u64], depth: Option<usize>) -> Comment {
+        let mut comment: Comment = serde_json::from_value(json!({
+            "id": 2921983,
+            "by": "norvig",
+            "parent": 2921506,
+            "text": text,
+            "kids": kids,
+            "time": 1314211127,
+            "type": "comment",
+        }))
+        .unwrap();
+        comment.depth = depth;
+        comment
+    }
+
+    static LONG: &str = "I'll keep writing if you keep reading.";
+
+    #[test]
+    fn deep_comments_collapse_from_the_threshold() {
+        let rules = CollapseRules::default();
+        assert!(!rules.collapses_alone(&comment(LONG, &[], Some(DEFAULT_COLLAPSE_DEPTH - 1))));
+        assert!(rules.collapses_alone(&comment(LONG, &[], Some(DEFAULT_COLLAPSE_DEPTH))));
+        assert!(!rules.collapses_alone(&comment(LONG, &[], None)));
+
+        let rules = CollapseRules {
+            depth: None,
+            ..rules
+        };
+        assert!(!rules.collapses_alone(&comment(LONG, &[], Some(100))));
+    }
+
+    #[test]
+    fn short_leaf_replies_collapse() {
+        let rules = CollapseRules::default();
+        assert!(rules.collapses_alone(&comment("<p>+1", &[], None)));
+        assert!(rules.collapses_alone(&comment("Thanks&#33;!!", &[], None)));
+        assert!(!rules.collapses_alone(&comment("+1", &[2922097], None)));
+        assert!(!rules.collapses_alone(&comment("Thanks a lot!", &[], None)));
+
+        let rules = CollapseRules {
+            short_reply_chars: 0,
+            ..rules
+        };
+        assert!(!rules.collapses_alone(&comment("", &[], None)));
+    }
+
+    #[test]
+    fn deleted_and_dead_comments_collapse() {
+        let mut deleted = comment(LONG, &[], None);
+        deleted.deleted = true;
+        let mut dead = comment(LONG, &[], None);
+        dead.dead = true;
+
+        let rules = CollapseRules::default();
+        assert!(rules.collapses_alone(&deleted));
+        assert!(rules.collapses_alone(&dead));
+    }
+}
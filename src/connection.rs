@@ -0,0 +1,322 @@
+//! Relay-style cursor pagination over lists of item ids.
+//!
+//! A cursor is the position of an edge in the underlying id list. HN's
+//! lists are re-ranked continually, so paging through a list that changed
+//! between requests may skip or repeat items.
+
+use crate::client::ItemDataLoader;
+use crate::result::{Error, Result};
+use crate::types::{Item, ItemId};
+use async_graphql::{connection::PageInfo, Context, SimpleObject};
+use std::future::Future;
+use std::ops::Range;
+
+/// The number of edges returned when `first` is omitted.
+pub const DEFAULT_PAGE_SIZE: usize = 10;
+
+/// The largest page served when no other maximum is configured.
+pub const DEFAULT_MAX_PAGE_SIZE: usize = 500;
+
+/// Server-wide limits on page sizes, registered as schema data.
+///
+/// Schemas without registered limits use the defaults.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct PageLimits {
+    /// The most entries a single page may hold.
+    pub max: usize,
+    /// The number of comments `kidsConnection` returns when `first` is
+    /// omitted, or `None` to return all of them up to `max`.
+    pub kids_default: Option<usize>,
+}
+
+impl Default for PageLimits {
+    fn default() -> Self {
+        Self {
+            max: DEFAULT_MAX_PAGE_SIZE,
+            kids_default: None,
+        }
+    }
+}
+
+impl PageLimits {
+    /// Read the limits from `HN_MAX_PAGE_SIZE` and `HN_KIDS_PAGE_SIZE`,
+    /// falling back to the defaults for unset or invalid values.
+    pub fn from_env() -> Self {
+        let var = |name| {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+        };
+        let defaults = Self::default();
+        Self {
+            max: var("HN_MAX_PAGE_SIZE").unwrap_or(defaults.max),
+            kids_default: var("HN_KIDS_PAGE_SIZE").or(defaults.kids_default),
+        }
+    }
+
+    /// Return the limits registered with the schema.
+    pub(crate) fn get(ctx: &Context<'_>) -> Self {
+        ctx.data_opt::<Self>().copied().unwrap_or_default()
+    }
+
+    /// Return the size of a `kidsConnection` page when `first` is omitted.
+    pub(crate) fn kids_page_size(&self) -> usize {
+        self.kids_default.unwrap_or(self.max).min(self.max)
+    }
+}
+
+/// An item together with its position in a list.
+#[derive(Debug, Clone, SimpleObject)]
+pub struct ItemEdge {
+    /// A cursor for use in `after`.
+    pub cursor: String,
+    /// The item at the end of the edge.
+    pub node: Item,
+}
+
+/// Return the range of positions in a list of `len` ids selected by the
+/// pagination arguments.
+///
+/// The page starts after the `after` cursor, skips a further `offset`
+/// positions, and is at most `first` long, capped at `max`.
+pub(crate) fn window(
+    len: usize,
+    after: Option<&str>,
+    offset: usize,
+    first: Option<usize>,
+    max: usize,
+) -> Result<Range<usize>> {
+    let after = match after {
+        Some(cursor) => cursor
+            .parse::<usize>()
+            .map_err(|_| Error::InvalidCursor(cursor.to_string()))?
+            .saturating_add(1),
+        None => 0,
+    };
+    let start = after.saturating_add(offset).min(len);
+    let count = first.unwrap_or(DEFAULT_PAGE_SIZE).min(max);
+    Ok(start..start.saturating_add(count).min(len))
+}
+
+/// Return up to `limit` of `values`, skipping the first `offset`.
+pub(crate) fn slice<T>(values: &[T], offset: usize, limit: usize) -> &[T] {
+    let start = offset.min(values.len());
+    &values[start..start + limit.min(values.len() - start)]
+}
+
+/// The most ids examined for a single page of a filtered list.
+pub const MAX_FILTER_SCAN: usize = 1000;
+
+/// Load up to `first` edges of the items from position `start` of `ids`
+/// on that `keep` accepts, together with the page's `PageInfo`.
+///
+/// Items are loaded in batches until the page is full, the list ends, or
+/// `MAX_FILTER_SCAN` ids were examined. The end cursor is the last position
+/// examined, so that paging on from it never examines an id twice, even
+/// when the page came back short or empty.
+pub(crate) async fn load_filtered_edges(
+    ctx: &Context<'_>,
+    ids: &[ItemId],
+    start: usize,
+    first: usize,
+    keep: impl Fn(&Item) -> bool,
+) -> (Vec<ItemEdge>, PageInfo) {
+    filter_edges(ids.len(), start, first, keep, |range| {
+        load_positioned(ctx, ids, range)
+    })
+    .await
+}
+
+/// The scan behind `load_filtered_edges`, over a list of `len` ids whose
+/// items at a range of positions `load` returns.
+async fn filter_edges<F, Fut>(
+    len: usize,
+    start: usize,
+    first: usize,
+    keep: impl Fn(&Item) -> bool,
+    load: F,
+) -> (Vec<ItemEdge>, PageInfo)
+where
+    F: Fn(Range<usize>) -> Fut,
+    Fut: Future<Output = Vec<(usize, Item)>>,
+{
+    let end = start.saturating_add(MAX_FILTER_SCAN).min(len);
+    let mut edges = Vec::new();
+    let mut position = start;
+
+    'scan: while edges.len() < first && position < end {
+        let batch = (first - edges.len()).max(DEFAULT_PAGE_SIZE);
+        let range = position..position.saturating_add(batch).min(end);
+        position = range.end;
+        for (at, node) in load(range).await {
+            if !keep(&node) {
+                continue;
+            }
+            edges.push(ItemEdge {
+                cursor: at.to_string(),
+                node,
+            });
+            if edges.len() == first {
+                position = at + 1;
+                break 'scan;
+            }
+        }
+    }
+
+    let page_info = PageInfo {
+        has_previous_page: start > 0,
+        has_next_page: position < len,
+        start_cursor: edges.first().map(|edge| edge.cursor.clone()),
+        end_cursor: (position > start).then(|| (position - 1).to_string()),
+    };
+    (edges, page_info)
+}
+
+/// Load the items at `range` of `ids` as edges, together with the page's
+/// `PageInfo`.
+///
+/// Ids that fail to load are skipped, but keep their position.
+pub(crate) async fn load_edges(
+    ctx: &Context<'_>,
+    ids: &[ItemId],
+    range: Range<usize>,
+) -> (Vec<ItemEdge>, PageInfo) {
+    let page_info = PageInfo {
+        has_previous_page: range.start > 0,
+        has_next_page: range.end < ids.len(),
+        start_cursor: (!range.is_empty()).then(|| range.start.to_string()),
+        end_cursor: (!range.is_empty()).then(|| (range.end - 1).to_string()),
+    };
+    let edges = load_positioned(ctx, ids, range)
+        .await
+        .into_iter()
+        .map(|(position, node)| ItemEdge {
+            cursor: position.to_string(),
+            node,
+        })
+        .collect();
+
+    (edges, page_info)
+}
+
+/// Load the items at `range` of `ids`, each with its position. Ids that
+/// fail to load are skipped.
+async fn load_positioned(
+    ctx: &Context<'_>,
+    ids: &[ItemId],
+    range: Range<usize>,
+) -> Vec<(usize, Item)> {
+    let ids = &ids[range.clone()];
+    let mut items = ctx
+        .data_unchecked::<ItemDataLoader>()
+        .load_many(ids.iter().copied())
+        .await
+        .unwrap();
+    range
+        .zip(ids)
+        .filter_map(|(position, id)| items.remove(id).map(|item| (position, item)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A list of `len` stories whose ids are their positions, with the
+    /// odd ones dead.
+    fn stories(len: usize) -> Vec<Item> {
+        (0..len)
+            .map(|id| {
+                let story = json!({"id": id, "type": "story", "time": 1000, "dead": id % 2 == 1});
+                serde_json::from_value(story).unwrap()
+            })
+            .collect()
+    }
+
+    async fn filter(
+        items: &[Item],
+        start: usize,
+        first: usize,
+        keep: impl Fn(&Item) -> bool,
+    ) -> (Vec<String>, PageInfo) {
+        let (edges, page_info) = filter_edges(items.len(), start, first, keep, |range| {
+            let page = range.clone().zip(items[range].iter().cloned()).collect();
+            async move { page }
+        })
+        .await;
+        (
+            edges.into_iter().map(|edge| edge.cursor).collect(),
+            page_info,
+        )
+    }
+
+    #[test]
+    fn window_pages_after_the_cursor() {
+        assert_eq!(window(20, None, 0, None, 500).unwrap(), 0..10);
+        assert_eq!(window(20, None, 0, Some(5), 500).unwrap(), 0..5);
+        assert_eq!(window(20, Some("4"), 0, Some(5), 500).unwrap(), 5..10);
+        assert_eq!(window(20, Some("4"), 2, Some(5), 500).unwrap(), 7..12);
+        assert_eq!(window(20, Some("17"), 0, Some(5), 500).unwrap(), 18..20);
+        assert_eq!(window(20, Some("30"), 0, Some(5), 500).unwrap(), 20..20);
+        assert_eq!(window(20, None, 0, Some(50), 8).unwrap(), 0..8);
+        assert!(window(20, Some("x"), 0, None, 500).is_err());
+    }
+
+    #[test]
+    fn slice_stays_within_bounds() {
+        let values = [1, 2, 3, 4, 5];
+        assert_eq!(slice(&values, 0, 2), &[1, 2]);
+        assert_eq!(slice(&values, 3, 10), &[4, 5]);
+        assert_eq!(slice(&values, 10, 2), &[] as &[i32]);
+        assert_eq!(slice(&values, 2, 0), &[] as &[i32]);
+    }
+
+    #[tokio::test]
+    async fn filtered_pages_end_at_the_last_edge_when_full() {
+        let items = stories(20);
+        let (cursors, page_info) = filter(&items, 0, 3, Item::is_live).await;
+        assert_eq!(cursors, ["0", "2", "4"]);
+        assert!(!page_info.has_previous_page);
+        assert!(page_info.has_next_page);
+        assert_eq!(page_info.start_cursor.as_deref(), Some("0"));
+        assert_eq!(page_info.end_cursor.as_deref(), Some("4"));
+
+        let (cursors, _) = filter(&items, 5, 2, Item::is_live).await;
+        assert_eq!(cursors, ["6", "8"]);
+    }
+
+    #[tokio::test]
+    async fn short_filtered_pages_end_at_the_last_position_examined() {
+        let items = stories(20);
+        let (cursors, page_info) = filter(&items, 15, 5, Item::is_live).await;
+        assert_eq!(cursors, ["16", "18"]);
+        assert!(page_info.has_previous_page);
+        assert!(!page_info.has_next_page);
+        assert_eq!(page_info.end_cursor.as_deref(), Some("19"));
+
+        let (cursors, page_info) = filter(&items, 0, 5, |_| false).await;
+        assert!(cursors.is_empty());
+        assert_eq!(page_info.start_cursor, None);
+        assert_eq!(page_info.end_cursor.as_deref(), Some("19"));
+    }
+
+    #[tokio::test]
+    async fn filtering_gives_up_after_the_scan_limit() {
+        let items = stories(MAX_FILTER_SCAN + 500);
+        let (cursors, page_info) = filter(&items, 0, 5, |_| false).await;
+        assert!(cursors.is_empty());
+        assert!(page_info.has_next_page);
+        let last = (MAX_FILTER_SCAN - 1).to_string();
+        assert_eq!(page_info.end_cursor.as_deref(), Some(last.as_str()));
+    }
+
+    #[tokio::test]
+    async fn filtering_past_the_end_is_empty() {
+        let items = stories(5);
+        let (cursors, page_info) = filter(&items, 5, 5, Item::is_live).await;
+        assert!(cursors.is_empty());
+        assert!(!page_info.has_next_page);
+        assert_eq!(page_info.end_cursor, None);
+    }
+}
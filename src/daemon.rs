@@ -0,0 +1,93 @@
+//! Detaching from the terminal, for running the binary directly on a
+//! Unix host rather than under a supervisor or in a container.
+//!
+//! Detaching forks, so it must happen before the Tokio runtime or any
+//! other thread is started.
+
+use crate::result::Result;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+/// Where a detached process writes its pid and its output.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Daemon {
+    /// The file to write the pid of the detached process to.
+    pub pidfile: Option<PathBuf>,
+    /// The file to append stdout and stderr to. Output is discarded
+    /// without one.
+    pub log_file: Option<PathBuf>,
+}
+
+impl Daemon {
+    /// Read the paths from `HN_PIDFILE` and `HN_LOG_FILE`.
+    pub fn from_env() -> Self {
+        Self {
+            pidfile: std::env::var_os("HN_PIDFILE").map(PathBuf::from),
+            log_file: std::env::var_os("HN_LOG_FILE").map(PathBuf::from),
+        }
+    }
+
+    /// Detach the process from its terminal and session, redirect its
+    /// standard streams, and write the pidfile.
+    ///
+    /// Returns in the detached process only; the original process exits.
+    /// The working directory is kept, so relative paths in the
+    /// configuration still resolve.
+    pub fn detach(&self) -> Result<()> {
+        // Open the log first, so that errors still reach the terminal.
+        let output = match &self.log_file {
+            Some(path) => OpenOptions::new().create(true).append(true).open(path)?,
+            None => OpenOptions::new().write(true).open("/dev/null")?,
+        };
+        let input = File::open("/dev/null")?;
+
+        // Fork twice: the first child leads a new session without a
+        // terminal, and the second can never acquire one again.
+        fork_and_exit_parent()?;
+        // SAFETY: setsid has no preconditions.
+        if unsafe { libc::setsid() } < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        fork_and_exit_parent()?;
+
+        redirect(&input, libc::STDIN_FILENO)?;
+        redirect(&output, libc::STDOUT_FILENO)?;
+        redirect(&output, libc::STDERR_FILENO)?;
+
+        if let Some(path) = &self.pidfile {
+            fs::write(path, format!("{}\n", std::process::id()))?;
+        }
+        Ok(())
+    }
+
+    /// Remove the pidfile, if any, ignoring failures.
+    pub fn remove_pidfile(&self) {
+        if let Some(path) = self.pidfile.as_deref().filter(|path| Path::exists(path)) {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Fork, exiting immediately in the parent.
+fn fork_and_exit_parent() -> Result<()> {
+    // SAFETY: the process is still single-threaded, so the child can run
+    // arbitrary code after the fork.
+    match unsafe { libc::fork() } {
+        -1 => Err(io::Error::last_os_error().into()),
+        0 => Ok(()),
+        // SAFETY: _exit skips destructors and atexit handlers, which
+        // belong to the child now.
+        _ => unsafe { libc::_exit(0) },
+    }
+}
+
+/// Point the descriptor `fd` at `file`.
+fn redirect(file: &File, fd: libc::c_int) -> Result<()> {
+    // SAFETY: both descriptors are valid for the duration of the call.
+    if unsafe { libc::dup2(file.as_raw_fd(), fd) } < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+    Ok(())
+}
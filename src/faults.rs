@@ -0,0 +1,149 @@
+//! Injection of upstream faults, for resilience testing in staging.
+//!
+//! An injector makes a random share of upstream calls slow, fail as if
+//! the API returned a 5xx status, or return a body that is not valid JSON,
+//! without waiting for a real upstream incident.
+
+use crate::result::{Error, Result};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// The body returned by calls injected with a malformed response.
+pub static MALFORMED_BODY: &[u8] = b"{\"id\": 8863, \"by\": ";
+
+/// The status code reported by calls injected with an upstream error.
+pub const INJECTED_STATUS: u16 = 503;
+
+/// The fault to apply to a single upstream call, after any delay.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Fault {
+    /// Return `MALFORMED_BODY` instead of the real response.
+    Malformed,
+}
+
+/// Probabilities of injecting each kind of fault into an upstream call.
+///
+/// Rates are between 0 and 1. A call may be both delayed and then fail.
+///
+/// With a fixed seed the injector draws the same sequence of faults on
+/// every run. Which call meets which fault still follows the order the
+/// calls are made in, so runs are only reproducible when that order is,
+/// for example with sequential requests against a replayed cassette.
+#[derive(Debug)]
+pub struct FaultInjector {
+    delay_rate: f64,
+    delay: Duration,
+    error_rate: f64,
+    malformed_rate: f64,
+    rng: Mutex<fastrand::Rng>,
+}
+
+impl Default for FaultInjector {
+    fn default() -> Self {
+        Self {
+            delay_rate: 0.0,
+            delay: Duration::from_secs(1),
+            error_rate: 0.0,
+            malformed_rate: 0.0,
+            rng: Mutex::new(fastrand::Rng::new()),
+        }
+    }
+}
+
+impl FaultInjector {
+    /// Create an injector that injects nothing yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Delay a share `rate` of calls by `delay`.
+    pub fn delay(self, rate: f64, delay: Duration) -> Self {
+        Self {
+            delay_rate: rate,
+            delay,
+            ..self
+        }
+    }
+
+    /// Fail a share `rate` of calls with an upstream error.
+    pub fn error(self, rate: f64) -> Self {
+        Self {
+            error_rate: rate,
+            ..self
+        }
+    }
+
+    /// Answer a share `rate` of calls with a malformed body.
+    pub fn malformed(self, rate: f64) -> Self {
+        Self {
+            malformed_rate: rate,
+            ..self
+        }
+    }
+
+    /// Draw faults from a generator seeded with `seed`, instead of a
+    /// randomly seeded one.
+    pub fn seed(self, seed: u64) -> Self {
+        Self {
+            rng: Mutex::new(fastrand::Rng::with_seed(seed)),
+            ..self
+        }
+    }
+
+    /// Read the injector from the environment, or return `None` if it
+    /// would inject nothing.
+    ///
+    /// `HN_FAULT_DELAY_RATE`, `HN_FAULT_ERROR_RATE` and
+    /// `HN_FAULT_MALFORMED_RATE` set the rates, `HN_FAULT_DELAY_MS` the
+    /// length of injected delays, and `HN_FAULT_SEED` fixes the seed.
+    /// Unset or invalid values leave the defaults.
+    pub fn from_env() -> Option<Self> {
+        fn var<T: std::str::FromStr>(name: &str) -> Option<T> {
+            std::env::var(name)
+                .ok()
+                .and_then(|value| value.parse().ok())
+        }
+        let defaults = Self::default();
+        let injector = Self {
+            delay_rate: var("HN_FAULT_DELAY_RATE").unwrap_or(defaults.delay_rate),
+            delay: var("HN_FAULT_DELAY_MS")
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.delay),
+            error_rate: var("HN_FAULT_ERROR_RATE").unwrap_or(defaults.error_rate),
+            malformed_rate: var("HN_FAULT_MALFORMED_RATE").unwrap_or(defaults.malformed_rate),
+            ..defaults
+        };
+        let injector = match var("HN_FAULT_SEED") {
+            Some(seed) => injector.seed(seed),
+            None => injector,
+        };
+        let injects = [
+            injector.delay_rate,
+            injector.error_rate,
+            injector.malformed_rate,
+        ]
+        .iter()
+        .any(|&rate| rate > 0.0);
+        injects.then_some(injector)
+    }
+
+    /// Apply the faults drawn for one upstream call: sleep if it is
+    /// delayed, fail if it errors, and return the fault to apply to its
+    /// response, if any.
+    pub async fn inject(&self) -> Result<Option<Fault>> {
+        if self.roll(self.delay_rate) {
+            tokio::time::sleep(self.delay).await;
+        }
+        if self.roll(self.error_rate) {
+            return Err(Error::InjectedFault(INJECTED_STATUS));
+        }
+        Ok(self.roll(self.malformed_rate).then_some(Fault::Malformed))
+    }
+
+    /// Return `true` with probability `rate`.
+    fn roll(&self, rate: f64) -> bool {
+        // Draw even for zero rates, so that the sequence of draws doesn't
+        // depend on which faults are enabled.
+        self.rng.lock().unwrap().f64() < rate
+    }
+}
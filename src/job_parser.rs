@@ -0,0 +1,149 @@
+//! Best-effort extraction of structured data from job postings.
+//!
+//! HN job posts follow loose conventions such as
+//! "Acme (YC S21) Is Hiring Backend Engineers in Berlin (Remote OK)", so
+//! the heuristics here are deliberately forgiving: anything they can't
+//! recognise is simply left out.
+
+use crate::textproc::{PlainText, Processor};
+use async_graphql::SimpleObject;
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Structured data extracted from a job posting's title and text.
+#[derive(Debug, Clone, Default, PartialEq, SimpleObject)]
+pub struct ParsedJob {
+    /// The name of the hiring company.
+    pub company: Option<String>,
+    /// Location hints, for example "Remote" or "San Francisco".
+    pub locations: Vec<String>,
+    /// Compensation mentions, for example "$120k-$160k".
+    pub compensation: Vec<String>,
+}
+
+fn company_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(?i)^\s*(.+?)\s*(?:\(YC [A-Z]\d{2}\)|\bis hiring\b|\bhiring\b)").unwrap()
+    })
+}
+
+fn location_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(?:\bin ([A-Z][\w.-]*(?:[ ,]+[A-Z][\w.-]*)*)|\(([^()]*)\))").unwrap()
+    })
+}
+
+fn remote_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?i)\bremote\b").unwrap())
+}
+
+fn compensation_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(r"(?i)[$€£]\s?\d[\d,.]*\s?k?(?:\s?(?:-|–|to)\s?[$€£]?\s?\d[\d,.]*\s?k?)?")
+            .unwrap()
+    })
+}
+
+/// Parse a job posting from its title and optional HTML text.
+pub fn parse(title: &str, text: Option<&str>) -> ParsedJob {
+    let text = text.map(|text| PlainText.process(text)).unwrap_or_default();
+
+    let company = company_re()
+        .captures(title)
+        .map(|caps| caps[1].trim().to_string())
+        .filter(|company| !company.is_empty());
+
+    let mut locations = Vec::new();
+    for caps in location_re().captures_iter(title) {
+        let hint = caps
+            .get(1)
+            .or_else(|| caps.get(2))
+            .map(|m| m.as_str().trim())
+            .unwrap_or_default();
+        // Parenthesised YC batches are not locations.
+        if hint.is_empty() || hint.starts_with("YC ") {
+            continue;
+        }
+        push_unique(&mut locations, hint);
+    }
+    let mentions_remote = remote_re().is_match(title) || remote_re().is_match(&text);
+    if mentions_remote && !locations.iter().any(|loc| remote_re().is_match(loc)) {
+        push_unique(&mut locations, "Remote");
+    }
+
+    let mut compensation = Vec::new();
+    for m in compensation_re()
+        .find_iter(title)
+        .chain(compensation_re().find_iter(&text))
+    {
+        push_unique(&mut compensation, m.as_str().trim());
+    }
+
+    ParsedJob {
+        company,
+        locations,
+        compensation,
+    }
+}
+
+fn push_unique(values: &mut Vec<String>, value: &str) {
+    if !values.iter().any(|existing| existing == value) {
+        values.push(value.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn company_precedes_the_batch_or_hiring() {
+        let parse_company = |title| parse(title, None).company;
+        assert_eq!(
+            parse_company("Flexport (YC W14) Is Hiring a Senior Software Engineer"),
+            Some("Flexport".to_string())
+        );
+        assert_eq!(
+            parse_company("Gitlab is hiring engineers"),
+            Some("Gitlab".to_string())
+        );
+    }
+
+    #[test]
+    fn locations_come_from_in_clauses_and_parentheses() {
+        let job = parse(
+            "Acme (YC S21) Is Hiring Backend Engineers in Berlin (Remote OK)",
+            None,
+        );
+        assert_eq!(job.locations, ["Berlin", "Remote OK"]);
+    }
+
+    #[test]
+    fn remote_in_the_text_adds_a_location() {
+        let job = parse(
+            "Acme Is Hiring Engineers in San Francisco",
+            Some("<p>Fully remote within the US.</p>"),
+        );
+        assert_eq!(job.locations, ["San Francisco", "Remote"]);
+    }
+
+    #[test]
+    fn compensation_ranges_are_kept_whole() {
+        let job = parse(
+            "Acme Is Hiring a Staff Engineer ($180k-$220k)",
+            Some("Salary: €70,000 to €90,000 plus equity."),
+        );
+        assert_eq!(job.compensation, ["$180k-$220k", "€70,000 to €90,000"]);
+    }
+
+    #[test]
+    fn unconventional_titles_yield_nothing() {
+        // The title of item 192327, recorded in tests/fixtures.
+        let job = parse("Justin.tv is looking for a Lead Flash Engineer!", None);
+        assert_eq!(job, ParsedJob::default());
+    }
+}
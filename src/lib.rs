@@ -0,0 +1,27 @@
+//! A GraphQL API for Hacker News, backed by the official Firebase API.
+
+#[cfg(feature = "cassette")]
+pub mod cassette;
+pub mod client;
+pub mod collapse;
+pub mod connection;
+#[cfg(all(unix, feature = "daemon"))]
+pub mod daemon;
+#[cfg(feature = "fault-injection")]
+pub mod faults;
+#[cfg(feature = "job-parsing")]
+mod job_parser;
+pub mod mirror;
+pub mod node;
+pub mod ranking;
+pub mod redaction;
+pub mod result;
+pub mod schema;
+pub mod server;
+pub mod stats;
+pub mod textproc;
+pub mod types;
+mod url_utils;
+pub mod usage;
+
+pub use schema::{build_schema, HnSchema, Query, SchemaBuilderExt};
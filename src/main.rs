@@ -1,84 +1,113 @@
-use async_graphql::http::{playground_source, GraphQLPlaygroundConfig};
-use async_graphql::{
-    dataloader::DataLoader, Context, EmptyMutation, EmptySubscription, Object, Schema,
-};
-use async_graphql_warp::{BadRequest, Response};
-use futures::future::join_all;
-use http::StatusCode;
-use std::convert::Infallible;
-use warp::{http::Response as HttpResponse, Filter, Rejection};
+use hn_gql::client::{user_agent, HnClient};
+use hn_gql::collapse::CollapseRules;
+use hn_gql::connection::PageLimits;
+use hn_gql::redaction::Redaction;
+use hn_gql::server::Server;
+use hn_gql::stats::BudgetClasses;
+use hn_gql::usage::FieldUsage;
+use hn_gql::{build_schema, SchemaBuilderExt};
 
-mod client;
-mod result;
-mod types;
-use client::{HnClient, ItemLoader};
-use result::Result;
-use types::*;
-
-#[tokio::main]
-async fn main() {
-    let client = HnClient::init().unwrap();
-
-    let schema = Schema::build(Query, EmptyMutation, EmptySubscription)
-        .data(client.clone())
-        .data(DataLoader::new(ItemLoader { client }))
-        .finish();
-
-    println!("Playground: http://localhost:8000");
+fn main() {
+    // Detach before the runtime starts any threads.
+    let cleanup = detach();
+    tokio::runtime::Runtime::new()
+        .unwrap()
+        .block_on(run(cleanup));
+}
 
-    let graphql_post = async_graphql_warp::graphql(schema).and_then(
-        |(schema, request): (
-            Schema<Query, EmptyMutation, EmptySubscription>,
-            async_graphql::Request,
-        )| async move { Ok::<_, Infallible>(Response::from(schema.execute(request).await)) },
-    );
+/// With `--daemon`, detach from the terminal and return the cleanup to
+/// run on shutdown.
+#[cfg(all(unix, feature = "daemon"))]
+fn detach() -> impl FnOnce() + Send + 'static {
+    let daemon = std::env::args()
+        .any(|arg| arg == "--daemon")
+        .then(hn_gql::daemon::Daemon::from_env);
+    if let Some(daemon) = &daemon {
+        daemon.detach().unwrap();
+    }
+    move || {
+        if let Some(daemon) = daemon {
+            daemon.remove_pidfile();
+        }
+    }
+}
 
-    let graphql_playground = warp::path::end().and(warp::get()).map(|| {
-        HttpResponse::builder()
-            .header("content-type", "text/html")
-            .body(playground_source(GraphQLPlaygroundConfig::new("/")))
-    });
+#[cfg(not(all(unix, feature = "daemon")))]
+fn detach() -> impl FnOnce() + Send + 'static {
+    if std::env::args().any(|arg| arg == "--daemon") {
+        eprintln!("--daemon needs a Unix build with the daemon feature");
+        std::process::exit(1);
+    }
+    || ()
+}
 
-    let routes = graphql_playground
-        .or(graphql_post)
-        .recover(|err: Rejection| async move {
-            if let Some(BadRequest(err)) = err.find() {
-                return Ok::<_, Infallible>(warp::reply::with_status(
-                    err.to_string(),
-                    StatusCode::BAD_REQUEST,
-                ));
-            }
+/// Wait for Ctrl-C or, on Unix, SIGTERM.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = signal(SignalKind::terminate()).unwrap();
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
 
-            Ok(warp::reply::with_status(
-                "INTERNAL_SERVER_ERROR".to_string(),
-                StatusCode::INTERNAL_SERVER_ERROR,
-            ))
-        });
+async fn run(cleanup: impl FnOnce() + Send + 'static) {
+    let user_agent = std::env::var("HN_USER_AGENT")
+        .unwrap_or_else(|_| user_agent(std::env::var("HN_CONTACT").ok().as_deref()));
+    let client = HnClient::init_with_user_agent(&user_agent).unwrap();
+    #[cfg(feature = "cassette")]
+    let client = match hn_gql::cassette::Cassette::from_env() {
+        Some(cassette) => client.with_cassette(cassette),
+        None => client,
+    };
+    #[cfg(feature = "fault-injection")]
+    let client = match hn_gql::faults::FaultInjector::from_env() {
+        Some(faults) => client.with_faults(faults),
+        None => client,
+    };
 
-    warp::serve(routes).run(([0, 0, 0, 0], 8000)).await;
-}
+    // Field usage is only collected, and its report only served, when
+    // asked for: the report reveals the operations of every client.
+    let usage = std::env::var_os("HN_FIELD_USAGE").map(|_| FieldUsage::new());
+    let builder = build_schema(client.clone())
+        .page_limits(PageLimits::from_env())
+        .collapse_rules(CollapseRules::from_env());
+    let builder = match &usage {
+        Some(usage) => builder.extension(usage.clone()),
+        None => builder,
+    };
+    let redaction = Redaction::from_env().unwrap();
+    let redacted = redaction.is_some();
+    let schema = match redaction {
+        Some(redaction) => builder.extension(redaction),
+        None => builder,
+    }
+    .finish();
 
-struct Query;
+    if std::env::args().any(|arg| arg == "--print-schema") {
+        print!("{}", schema.sdl());
+        return;
+    }
 
-#[Object]
-impl Query {
-    async fn top(&self, ctx: &Context<'_>, limit: Option<usize>) -> Result<Vec<Item>> {
-        let client = ctx.data_unchecked::<HnClient>();
-        let limit = limit.unwrap_or(10);
-        let ids = client
-            .get_top_stories()
-            .await?
-            .into_iter()
-            .take(limit)
-            .collect::<Vec<_>>();
+    let budgets = BudgetClasses::from_env().unwrap();
 
-        Ok(ctx
-            .data_unchecked::<DataLoader<ItemLoader>>()
-            .load_many(ids)
-            .await
-            .unwrap()
-            .into_iter()
-            .map(|(_, v)| v)
-            .collect())
+    let server = Server::builder(schema, client)
+        .budget_classes(budgets)
+        .mirror(!redacted);
+    let server = match usage {
+        Some(usage) => server.field_usage(usage),
+        None => server,
     }
+    .on_startup(|addr| println!("Playground: http://localhost:{}", addr.port()))
+    .on_shutdown(cleanup)
+    .start()
+    .unwrap();
+
+    shutdown_signal().await;
+    server.shutdown().await;
 }
@@ -0,0 +1,67 @@
+//! Routes mirroring the shape of the official Firebase API.
+//!
+//! Tools written against `https://hacker-news.firebaseio.com/v0` can point
+//! at this server instead. Responses are passed through untouched, so
+//! missing items are still returned as `null`.
+
+use crate::client::HnClient;
+use crate::types::Username;
+use http::StatusCode;
+use warp::{path::Tail, Filter, Rejection, Reply};
+
+/// The list documents served under `/v0`.
+static LISTS: &[&str] = &[
+    "topstories.json",
+    "newstories.json",
+    "beststories.json",
+    "askstories.json",
+    "showstories.json",
+    "jobstories.json",
+    "maxitem.json",
+    "updates.json",
+];
+
+/// Return whether `path` names a document served by the Firebase API.
+fn is_mirrored(path: &str) -> bool {
+    if LISTS.contains(&path) {
+        return true;
+    }
+
+    let (kind, name) = match path.split_once('/') {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let name = match name.strip_suffix(".json") {
+        Some(name) if !name.is_empty() => name,
+        _ => return false,
+    };
+    match kind {
+        "item" => name.bytes().all(|b| b.is_ascii_digit()),
+        "user" => Username::is_valid(name),
+        _ => false,
+    }
+}
+
+/// Build the `/v0/...` passthrough routes.
+pub fn routes(client: HnClient) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    warp::path("v0")
+        .and(warp::get())
+        .and(warp::path::tail())
+        .and_then(move |tail: Tail| {
+            let client = client.clone();
+            async move {
+                if !is_mirrored(tail.as_str()) {
+                    return Err(warp::reject::not_found());
+                }
+
+                let reply = match client.get_raw(tail.as_str()).await {
+                    Ok(json) => warp::reply::with_status(warp::reply::json(&json), StatusCode::OK),
+                    Err(err) => warp::reply::with_status(
+                        warp::reply::json(&err.to_string()),
+                        StatusCode::BAD_GATEWAY,
+                    ),
+                };
+                Ok(reply)
+            }
+        })
+}
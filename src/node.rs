@@ -0,0 +1,104 @@
+//! The Relay `Node` interface and global ids.
+//!
+//! A global id is the base64 encoding of `<Type>:<key>`, for example
+//! `Story:8863` or `User:jl`. The `id` field of items is already taken by
+//! their `ItemId`, so nodes expose their global id as `nodeId`; Relay
+//! clients should set `nodeInterfaceIdField` to `nodeId`.
+
+use crate::result::Result;
+use crate::types::{
+    load_items, load_user, Comment, Item, ItemId, Job, Poll, Pollopt, Story, UnknownItem, User,
+    Username,
+};
+use async_graphql::{Context, Interface, ID};
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+/// An object with a global id.
+#[allow(clippy::duplicated_attributes)]
+#[derive(Interface)]
+#[graphql(field(name = "node_id", type = "ID"))]
+pub enum Node {
+    /// A story.
+    Story(Story),
+    /// A comment.
+    Comment(Comment),
+    /// A job.
+    Job(Job),
+    /// A poll.
+    Poll(Poll),
+    /// A poll option belonging to a poll.
+    Pollopt(Pollopt),
+    /// An item that could not be decoded.
+    UnknownItem(UnknownItem),
+    /// A user profile.
+    User(User),
+}
+
+impl From<Item> for Node {
+    fn from(item: Item) -> Self {
+        match item {
+            Item::Story(story) => Node::Story(story),
+            Item::Comment(comment) => Node::Comment(comment),
+            Item::Job(job) => Node::Job(job),
+            Item::Poll(poll) => Node::Poll(poll),
+            Item::Pollopt(pollopt) => Node::Pollopt(pollopt),
+            Item::Unknown(unknown) => Node::UnknownItem(unknown),
+        }
+    }
+}
+
+/// Return the global id of the object of type `type_name` with `key`.
+pub(crate) fn global_id(type_name: &str, key: impl std::fmt::Display) -> ID {
+    ID(STANDARD.encode(format!("{}:{}", type_name, key)))
+}
+
+/// Split a global id into its type name and key, or return `None` if it
+/// is malformed.
+pub(crate) fn decode(id: &str) -> Option<(String, String)> {
+    let decoded = String::from_utf8(STANDARD.decode(id).ok()?).ok()?;
+    let (type_name, key) = decoded.split_once(':')?;
+    Some((type_name.to_string(), key.to_string()))
+}
+
+/// Load the node with global id `id`, or return `None` if it is malformed
+/// or names nothing.
+pub(crate) async fn load(ctx: &Context<'_>, id: &str) -> Result<Option<Node>> {
+    let (type_name, key) = match decode(id) {
+        Some(parts) => parts,
+        None => return Ok(None),
+    };
+
+    if type_name == "User" {
+        if !Username::is_valid(&key) {
+            return Ok(None);
+        }
+        return Ok(load_user(ctx, &Username(key)).await.map(Node::User));
+    }
+
+    let id = match key.parse() {
+        Ok(id) => ItemId(id),
+        Err(_) => return Ok(None),
+    };
+    // Reject ids naming an item under the wrong type, so that every
+    // object has exactly one global id.
+    Ok(load_items(ctx, &[id])
+        .await
+        .pop()
+        .map(Node::from)
+        .filter(|node| node.kind() == type_name))
+}
+
+impl Node {
+    /// The GraphQL type name of the node, as used in its global id.
+    fn kind(&self) -> &'static str {
+        match self {
+            Node::Story(_) => "Story",
+            Node::Comment(_) => "Comment",
+            Node::Job(_) => "Job",
+            Node::Poll(_) => "Poll",
+            Node::Pollopt(_) => "Pollopt",
+            Node::UnknownItem(_) => "UnknownItem",
+            Node::User(_) => "User",
+        }
+    }
+}
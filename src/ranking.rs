@@ -0,0 +1,67 @@
+//! Ranking strategies for the `frontPage` query.
+
+use crate::types::Item;
+use async_graphql::{Enum, InputObject};
+
+/// How `frontPage` orders stories.
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RankingStrategy {
+    /// HN's classic gravity formula: `(score - 1) / (hours + 2)^1.8`.
+    HnGravity,
+    /// Highest score first, regardless of age.
+    ScoreOnly,
+    /// Most comments per hour since posting first.
+    CommentVelocity,
+    /// The gravity formula with the weights given in `RankingWeights`.
+    Custom,
+}
+
+/// Weights for the `CUSTOM` ranking strategy.
+///
+/// Stories are ranked by
+/// `(score * (points - 1) + comments * descendants) / (hours + 2)^gravity`.
+#[derive(InputObject, Copy, Clone, Debug)]
+pub struct RankingWeights {
+    /// The weight of a story's points.
+    #[graphql(default_with = "1.0")]
+    pub score: f64,
+    /// The weight of a story's comment count.
+    #[graphql(default_with = "0.0")]
+    pub comments: f64,
+    /// How quickly stories sink with age.
+    #[graphql(default_with = "1.8")]
+    pub gravity: f64,
+}
+
+impl Default for RankingWeights {
+    fn default() -> Self {
+        Self {
+            score: 1.0,
+            comments: 0.0,
+            gravity: 1.8,
+        }
+    }
+}
+
+/// Return the rank of `item` at time `now` (Unix Time); higher ranks first.
+pub fn rank(item: &Item, strategy: RankingStrategy, weights: RankingWeights, now: u64) -> f64 {
+    let (score, descendants, time) = match item {
+        Item::Story(story) => (story.score, story.descendants.unwrap_or(0), story.time),
+        Item::Poll(poll) => (poll.score, poll.descendants.unwrap_or(0), poll.time),
+        Item::Job(job) => (job.score, 0, job.time),
+        Item::Comment(_) | Item::Pollopt(_) | Item::Unknown(_) => return f64::MIN,
+    };
+    let points = f64::from(score);
+    let comments = f64::from(descendants);
+    let hours = now.saturating_sub(time) as f64 / 3600.0;
+
+    match strategy {
+        RankingStrategy::HnGravity => (points - 1.0) / (hours + 2.0).powf(1.8),
+        RankingStrategy::ScoreOnly => points,
+        RankingStrategy::CommentVelocity => comments / (hours + 2.0),
+        RankingStrategy::Custom => {
+            (weights.score * (points - 1.0) + weights.comments * comments)
+                / (hours + 2.0).powf(weights.gravity)
+        }
+    }
+}
@@ -0,0 +1,250 @@
+//! Redaction of selected response fields, for deployments serving
+//! anonymized data.
+//!
+//! Rules name a field as `Type.field`, where `Type` may be `*` to match
+//! the field on every type. Redaction happens as fields are resolved, so
+//! it covers every path to a field without changing the schema.
+//!
+//! Fields selected on an interface are redacted if the field is redacted
+//! on any of the interface's implementations, since the object they are
+//! resolved on is not known. Redacted values keep their field's type. A node's `nodeId` embeds its
+//! `id`, so it is derived from the redacted `id` whenever that is
+//! redacted. `UnknownItem.raw` holds the whole upstream item and is
+//! blanked as soon as any rule is configured.
+
+use crate::node;
+use crate::result::{Error, Result};
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextResolve, ResolveInfo,
+};
+use async_graphql::registry::MetaType;
+use async_graphql::{ServerError, ServerResult, Value};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+/// What to replace a redacted value with.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum RedactionMode {
+    /// Strings become empty, lists become empty, and anything else
+    /// becomes `null`. Non-null numbers and booleans become `0` and
+    /// `false` instead.
+    Blank,
+    /// Strings and numbers are replaced by a salted hash of the same
+    /// kind, so equal values still compare equal. Lists are hashed element
+    /// by element.
+    Hash,
+}
+
+/// A redacted field.
+#[derive(Debug, Clone)]
+struct Rule {
+    type_name: String,
+    field: String,
+    mode: RedactionMode,
+}
+
+/// A set of fields to redact, usable as a schema extension.
+#[derive(Debug, Clone, Default)]
+pub struct Redaction {
+    rules: Vec<Rule>,
+    salt: String,
+}
+
+impl Redaction {
+    /// Create a redaction that redacts nothing yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Redact `field` of `type_name` (or of every type, for `*`) with
+    /// `mode`.
+    pub fn field(mut self, type_name: &str, field: &str, mode: RedactionMode) -> Self {
+        self.rules.push(Rule {
+            type_name: type_name.to_string(),
+            field: field.to_string(),
+            mode,
+        });
+        self
+    }
+
+    /// Mix `salt` into hashes, so that hashed values can't be recovered
+    /// by hashing guesses.
+    pub fn salt(self, salt: &str) -> Self {
+        Self {
+            salt: salt.to_string(),
+            ..self
+        }
+    }
+
+    /// Read the redaction rules from the environment, or return `None` if
+    /// none are configured.
+    ///
+    /// `HN_REDACT` is a comma-separated list of `Type.field:mode` rules,
+    /// where mode is `blank` or `hash`, for example
+    /// `*.by:hash,User.about:blank`. `HN_REDACT_SALT` sets the salt.
+    pub fn from_env() -> Result<Option<Self>> {
+        let rules = match std::env::var("HN_REDACT") {
+            Ok(rules) => rules,
+            Err(_) => return Ok(None),
+        };
+        let mut redaction = Self::new().salt(&std::env::var("HN_REDACT_SALT").unwrap_or_default());
+        for rule in rules
+            .split(',')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+        {
+            let (type_name, field, mode) =
+                parse_rule(rule).ok_or_else(|| Error::InvalidRedactionRule(rule.to_string()))?;
+            redaction = redaction.field(type_name, field, mode);
+        }
+        Ok(Some(redaction))
+    }
+
+    /// Return how `field` is redacted on an object of one of
+    /// `type_names`, if at all.
+    fn mode(&self, type_names: &[&str], field: &str) -> Option<RedactionMode> {
+        let rule = self.rules.iter().find(|rule| {
+            (rule.type_name == "*" || type_names.contains(&rule.type_name.as_str()))
+                && rule.field == field
+        });
+        match rule {
+            Some(rule) => Some(rule.mode),
+            None if !self.rules.is_empty()
+                && type_names.contains(&"UnknownItem")
+                && field == "raw" =>
+            {
+                Some(RedactionMode::Blank)
+            }
+            None => None,
+        }
+    }
+
+    /// Redact `value` with `mode`, or return `None` if the result would
+    /// not be a valid value of the field's type.
+    fn redact(&self, value: Value, mode: RedactionMode, nullable: bool) -> Option<Value> {
+        Some(match (value, mode) {
+            (Value::Null, _) => Value::Null,
+            (Value::List(values), RedactionMode::Hash) => Value::List(
+                values
+                    .into_iter()
+                    .map(|value| self.redact(value, mode, nullable))
+                    .collect::<Option<_>>()?,
+            ),
+            (Value::String(s), RedactionMode::Hash) => Value::String(self.hash(&s)),
+            (Value::Number(n), RedactionMode::Hash) => {
+                Value::from(self.hash_number(&n.to_string()))
+            }
+            (Value::String(_), RedactionMode::Blank) => Value::String(String::new()),
+            (Value::List(_), RedactionMode::Blank) => Value::List(Vec::new()),
+            (_, RedactionMode::Blank) if nullable => Value::Null,
+            (Value::Number(_), RedactionMode::Blank) => Value::from(0),
+            (Value::Boolean(_), RedactionMode::Blank) => Value::from(false),
+            _ => return None,
+        })
+    }
+
+    /// Redact the global id `node_id` as if its key were redacted as an
+    /// `id` field with `mode`.
+    fn redact_node_id(&self, node_id: Value, mode: RedactionMode) -> Option<Value> {
+        let node_id = match node_id {
+            Value::String(node_id) => node_id,
+            value => return Some(value),
+        };
+        let (type_name, key) = node::decode(&node_id)?;
+        // Ids and usernames are both serialized as strings.
+        let key = match self.redact(Value::String(key), mode, false)? {
+            Value::String(key) => key,
+            key => key.to_string(),
+        };
+        Some(Value::String(node::global_id(&type_name, key).0))
+    }
+
+    fn digest(&self, value: &str) -> [u8; 8] {
+        let digest = Sha256::new()
+            .chain_update(&self.salt)
+            .chain_update([0])
+            .chain_update(value)
+            .finalize();
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(&digest[..8]);
+        bytes
+    }
+
+    fn hash(&self, value: &str) -> String {
+        self.digest(value)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+
+    /// Hash `value` to a number that fits a GraphQL `Int`.
+    fn hash_number(&self, value: &str) -> i32 {
+        let digest = self.digest(value);
+        i32::from_be_bytes([digest[0], digest[1], digest[2], digest[3]]) & i32::MAX
+    }
+}
+
+/// Parse a `Type.field:mode` rule.
+fn parse_rule(rule: &str) -> Option<(&str, &str, RedactionMode)> {
+    let (path, mode) = rule.split_once(':')?;
+    let (type_name, field) = path.split_once('.')?;
+    let mode = match mode {
+        "blank" => RedactionMode::Blank,
+        "hash" => RedactionMode::Hash,
+        _ => return None,
+    };
+    Some((type_name, field, mode))
+}
+
+impl ExtensionFactory for Redaction {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(RedactionExtension(self.clone()))
+    }
+}
+
+struct RedactionExtension(Redaction);
+
+#[async_trait::async_trait]
+impl Extension for RedactionExtension {
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        let redaction = &self.0;
+        let (type_name, field) = (info.parent_type, info.name);
+        let nullable = !info.return_type.ends_with('!');
+
+        // A field selected on an interface doesn't say which object it is
+        // resolved on, so it follows the rules of every implementation.
+        let mut type_names = vec![type_name];
+        if let Some(MetaType::Interface { possible_types, .. }) =
+            ctx.schema_env.registry.types.get(type_name)
+        {
+            type_names.extend(possible_types.iter().map(String::as_str));
+        }
+
+        let mode = redaction.mode(&type_names, field);
+        let id_mode = match field {
+            "nodeId" if mode.is_none() => redaction.mode(&type_names, "id"),
+            _ => None,
+        };
+        let value = match next.run(ctx, info).await? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        let redacted = match (mode, id_mode) {
+            (Some(mode), _) => redaction.redact(value, mode, nullable),
+            (None, Some(mode)) => redaction.redact_node_id(value, mode),
+            (None, None) => Some(value),
+        };
+        redacted.map(Some).ok_or_else(|| {
+            ServerError::new(
+                format!("{}.{} can't be redacted as configured", type_name, field),
+                None,
+            )
+        })
+    }
+}
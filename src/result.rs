@@ -11,4 +11,36 @@ pub enum Error {
     /// ReqwestError
     #[error(transparent)]
     ReqwestError(#[from] reqwest::Error),
+    /// JsonError
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+    /// IoError
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
+    /// WarpError
+    #[error(transparent)]
+    WarpError(#[from] warp::Error),
+    /// UpstreamBudgetExceeded
+    #[error("upstream call budget of {0} calls exceeded")]
+    UpstreamBudgetExceeded(usize),
+    /// InvalidBudgetClass
+    #[error("invalid upstream budget {0:?}; expected a number or name=number")]
+    InvalidBudgetClass(String),
+    /// InvalidCursor
+    #[error("invalid cursor {0:?}")]
+    InvalidCursor(String),
+    /// EmptyBucket
+    #[error("activityTimeline buckets must be at least one second long")]
+    EmptyBucket,
+    /// InvalidRedactionRule
+    #[error("invalid redaction rule {0:?}; expected Type.field:blank or Type.field:hash")]
+    InvalidRedactionRule(String),
+    /// InvalidCassettePath
+    #[cfg(feature = "cassette")]
+    #[error("cassette path {0:?} is outside the cassette")]
+    InvalidCassettePath(String),
+    /// InjectedFault
+    #[cfg(feature = "fault-injection")]
+    #[error("injected upstream fault (HTTP {0})")]
+    InjectedFault(u16),
 }
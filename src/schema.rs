@@ -0,0 +1,568 @@
+//! The GraphQL schema and helpers for embedding it.
+
+use crate::client::{HnClient, ItemDataLoader, ItemLoader, UserLoader};
+use crate::collapse::CollapseRules;
+use crate::connection::{self, PageLimits};
+use crate::node::{self, Node};
+use crate::ranking::{rank, RankingStrategy, RankingWeights};
+use crate::result::Result;
+use crate::stats::{BudgetGuard, CountingCache};
+use crate::textproc::{process_all, Markdown, PlainText, Sanitize};
+use crate::types::*;
+use crate::url_utils;
+use async_graphql::{
+    dataloader::DataLoader, Context, EmptyMutation, EmptySubscription, Object, Schema,
+    SchemaBuilder, ID,
+};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+/// The schema served by this crate.
+pub type HnSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// Start building the schema with the client and item loader registered.
+///
+/// Embedders can register their own extensions and context data on the
+/// returned builder before calling `finish()`.
+pub fn build_schema(client: HnClient) -> SchemaBuilder<Query, EmptyMutation, EmptySubscription> {
+    Schema::build(Query, EmptyMutation, EmptySubscription).hn_client(client)
+}
+
+/// Extension methods for composing this crate's schema with other stacks.
+pub trait SchemaBuilderExt: Sized {
+    /// Register `client` and the `ItemLoader` and `UserLoader` built on it,
+    /// along with the `BudgetGuard` checking queries against upstream
+    /// budgets.
+    fn hn_client(self, client: HnClient) -> Self;
+
+    /// Register server-wide page size limits, replacing the defaults.
+    fn page_limits(self, limits: PageLimits) -> Self;
+
+    /// Register thresholds for `Comment.collapsedByDefault`, replacing the
+    /// defaults.
+    fn collapse_rules(self, rules: CollapseRules) -> Self;
+
+    /// Register a custom loader, available to resolvers as `DataLoader<T>`.
+    fn loader<T: Send + Sync + 'static>(self, loader: T) -> Self;
+}
+
+impl<Query, Mutation, Subscription> SchemaBuilderExt
+    for SchemaBuilder<Query, Mutation, Subscription>
+{
+    fn hn_client(self, client: HnClient) -> Self {
+        // Loaders shared by every request can't cache, or they would serve
+        // stale items forever. The server replaces them with caching
+        // loaders of its own for each request.
+        let items = DataLoader::with_cache(
+            ItemLoader {
+                client: client.clone(),
+            },
+            CountingCache::default(),
+        );
+        items.enable_all_cache(false);
+        let users = DataLoader::with_cache(
+            UserLoader {
+                client: client.clone(),
+            },
+            CountingCache::default(),
+        );
+        users.enable_all_cache(false);
+        self.data(client)
+            .data(items)
+            .data(users)
+            .extension(BudgetGuard)
+    }
+
+    fn page_limits(self, limits: PageLimits) -> Self {
+        self.data(limits)
+    }
+
+    fn collapse_rules(self, rules: CollapseRules) -> Self {
+        self.data(rules)
+    }
+
+    fn loader<T: Send + Sync + 'static>(self, loader: T) -> Self {
+        self.data(DataLoader::new(loader))
+    }
+}
+
+/// The root query type.
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// The object with the given global id, or `null` if there is none.
+    async fn node(&self, ctx: &Context<'_>, id: ID) -> Result<Option<Node>> {
+        node::load(ctx, &id).await
+    }
+
+    /// The item with the given id, or `null` if it does not exist.
+    async fn item(&self, ctx: &Context<'_>, id: ItemId) -> Option<Item> {
+        load_items(ctx, &[id]).await.pop()
+    }
+
+    /// Every submission of `url` found through HN Search, merged into one
+    /// view: the highest-scored submission and the reposts.
+    ///
+    /// URLs are compared after normalization, so tracking parameters and
+    /// trailing slashes don't matter. `null` if no submission was found.
+    async fn canonical_story(
+        &self,
+        ctx: &Context<'_>,
+        url: String,
+    ) -> Result<Option<CanonicalStory>> {
+        let canonical = match url_utils::canonicalize(&url) {
+            Some(canonical) => canonical,
+            None => return Ok(None),
+        };
+        let ids = ctx
+            .data_unchecked::<HnClient>()
+            .search_story_ids_by_url(&url, CANONICAL_SEARCH_HITS)
+            .await?;
+
+        let mut stories = load_items(ctx, &ids)
+            .await
+            .into_iter()
+            .filter_map(|item| match item {
+                Item::Story(story) => Some(story),
+                _ => None,
+            })
+            .filter(|story| {
+                story
+                    .url
+                    .as_deref()
+                    .and_then(url_utils::canonicalize)
+                    .as_ref()
+                    == Some(&canonical)
+            })
+            .collect::<Vec<_>>();
+        stories.sort_by_key(|story| Reverse(story.score));
+
+        let mut stories = stories.into_iter();
+        Ok(stories.next().map(|story| CanonicalStory {
+            story,
+            other_submissions: stories.collect(),
+        }))
+    }
+
+    /// The story with the given id and its comment tree, with counts that
+    /// are checked against each other. `depth`, `perLevelLimit` and
+    /// `includeDead` shape the tree as in `Story.commentTree`.
+    ///
+    /// The story is fetched once for this field, so the thread's `story`
+    /// and counts describe the same snapshot; other fields of the query
+    /// may see a different one. `null` if the id does not name a story.
+    async fn story_thread(
+        &self,
+        ctx: &Context<'_>,
+        id: ItemId,
+        depth: Option<usize>,
+        per_level_limit: Option<usize>,
+        include_dead: Option<bool>,
+    ) -> Option<StoryThread> {
+        match load_items(ctx, &[id]).await.pop() {
+            Some(Item::Story(story)) => Some(
+                StoryThread::load(
+                    ctx,
+                    story,
+                    depth,
+                    per_level_limit,
+                    include_dead.unwrap_or(false),
+                )
+                .await,
+            ),
+            _ => None,
+        }
+    }
+
+    /// The comment with the given id together with `ancestors` levels of
+    /// parent comments (default 1), the item at the top of its thread, and
+    /// `replies` levels of replies (default 1), for rendering permalinks.
+    ///
+    /// `null` if the id does not name a comment.
+    async fn comment_with_context(
+        &self,
+        ctx: &Context<'_>,
+        id: ItemId,
+        ancestors: Option<usize>,
+        replies: Option<usize>,
+        include_dead: Option<bool>,
+    ) -> Option<CommentContext> {
+        match load_items(ctx, &[id]).await.pop() {
+            Some(Item::Comment(comment)) => Some(
+                CommentContext::load(
+                    ctx,
+                    comment,
+                    ancestors.unwrap_or(1),
+                    replies.unwrap_or(1),
+                    include_dead.unwrap_or(false),
+                )
+                .await,
+            ),
+            _ => None,
+        }
+    }
+
+    /// The items with the given ids, in one batch.
+    ///
+    /// The result lines up with `ids`: items that do not exist are `null`.
+    async fn items(&self, ctx: &Context<'_>, ids: Vec<ItemId>) -> Vec<Option<Item>> {
+        let items = ctx
+            .data_unchecked::<ItemDataLoader>()
+            .load_many(ids.iter().copied())
+            .await
+            .unwrap();
+
+        ids.iter().map(|id| items.get(id).cloned()).collect()
+    }
+
+    /// The text of the items with the given ids, converted to `format`.
+    ///
+    /// Meant for bulk exports: the texts are converted in parallel, off the
+    /// threads serving requests. The result lines up with `ids`: items that
+    /// do not exist or have no text are `null`.
+    async fn rendered_texts(
+        &self,
+        ctx: &Context<'_>,
+        ids: Vec<ItemId>,
+        format: TextFormat,
+    ) -> Vec<Option<String>> {
+        let items = ctx
+            .data_unchecked::<ItemDataLoader>()
+            .load_many(ids.iter().copied())
+            .await
+            .unwrap();
+
+        let (positions, fragments): (Vec<_>, Vec<_>) = ids
+            .iter()
+            .enumerate()
+            .filter_map(|(i, id)| Some((i, items.get(id)?.text()?.to_string())))
+            .unzip();
+        let outputs = match format {
+            TextFormat::PlainText => process_all(PlainText, fragments).await,
+            TextFormat::Markdown => process_all(Markdown, fragments).await,
+            TextFormat::Html => process_all(Sanitize, fragments).await,
+        };
+
+        let mut texts = vec![None; ids.len()];
+        for (i, text) in positions.into_iter().zip(outputs) {
+            texts[i] = Some(text);
+        }
+        texts
+    }
+
+    /// The id of the newest item. Older items can be walked by counting
+    /// down from it.
+    async fn max_item(&self, ctx: &Context<'_>) -> Result<ItemId> {
+        ctx.data_unchecked::<HnClient>().get_max_item_id().await
+    }
+
+    /// Up to `limit` items with ids greater than `id`, oldest first, for
+    /// polling the newest items: pass the id of the last item seen.
+    ///
+    /// `limit` defaults to 10 and is capped at the server's maximum page
+    /// size. Items newer than `maxItem` don't exist yet, so the list comes
+    /// back short or empty when caught up. Ids that fail to load are
+    /// skipped.
+    async fn items_after(
+        &self,
+        ctx: &Context<'_>,
+        id: ItemId,
+        limit: Option<usize>,
+    ) -> Result<Vec<Item>> {
+        let limit = limit
+            .unwrap_or(connection::DEFAULT_PAGE_SIZE)
+            .min(PageLimits::get(ctx).max) as u64;
+        let max = ctx.data_unchecked::<HnClient>().get_max_item_id().await?;
+        let ids = (id.0.saturating_add(1)..=max.0.min(id.0.saturating_add(limit)))
+            .map(ItemId)
+            .collect::<Vec<_>>();
+        Ok(load_items(ctx, &ids).await)
+    }
+
+    /// The items and profiles that changed recently.
+    async fn updates(&self, ctx: &Context<'_>) -> Result<Updates> {
+        ctx.data_unchecked::<HnClient>().get_updates().await
+    }
+
+    /// The user with the given username, or `null` if there is none.
+    async fn user(&self, ctx: &Context<'_>, id: Username) -> Option<User> {
+        load_user(ctx, &id).await
+    }
+
+    /// A page of the stories in `list`.
+    ///
+    /// The page starts after the `after` cursor, skipping a further
+    /// `offset` stories, and holds `first` stories (default 10, at most
+    /// 500).
+    ///
+    /// Deleted and dead stories are skipped unless `includeDead` is true;
+    /// a page then examines at most 1000 stories, as in
+    /// `User.submittedConnection`.
+    async fn stories(
+        &self,
+        ctx: &Context<'_>,
+        list: StoryList,
+        first: Option<usize>,
+        after: Option<String>,
+        offset: Option<usize>,
+        include_dead: Option<bool>,
+    ) -> Result<StoryConnection> {
+        story_list(ctx, list, first, after, offset, include_dead).await
+    }
+
+    /// The stories on the front page, as with `stories(list: TOP)`.
+    async fn top(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<usize>,
+        after: Option<String>,
+        offset: Option<usize>,
+        include_dead: Option<bool>,
+    ) -> Result<StoryConnection> {
+        story_list(ctx, StoryList::Top, first, after, offset, include_dead).await
+    }
+
+    /// The newest stories, as with `stories(list: NEW)`.
+    #[graphql(name = "new")]
+    async fn new_stories(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<usize>,
+        after: Option<String>,
+        offset: Option<usize>,
+        include_dead: Option<bool>,
+    ) -> Result<StoryConnection> {
+        story_list(ctx, StoryList::New, first, after, offset, include_dead).await
+    }
+
+    /// The best stories of late, as with `stories(list: BEST)`.
+    async fn best(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<usize>,
+        after: Option<String>,
+        offset: Option<usize>,
+        include_dead: Option<bool>,
+    ) -> Result<StoryConnection> {
+        story_list(ctx, StoryList::Best, first, after, offset, include_dead).await
+    }
+
+    /// The latest Ask HN stories, as with `stories(list: ASK)`.
+    async fn ask(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<usize>,
+        after: Option<String>,
+        offset: Option<usize>,
+        include_dead: Option<bool>,
+    ) -> Result<StoryConnection> {
+        story_list(ctx, StoryList::Ask, first, after, offset, include_dead).await
+    }
+
+    /// The latest Show HN stories, as with `stories(list: SHOW)`.
+    async fn show(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<usize>,
+        after: Option<String>,
+        offset: Option<usize>,
+        include_dead: Option<bool>,
+    ) -> Result<StoryConnection> {
+        story_list(ctx, StoryList::Show, first, after, offset, include_dead).await
+    }
+
+    /// The latest job postings, as with `stories(list: JOB)`.
+    async fn jobs(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<usize>,
+        after: Option<String>,
+        offset: Option<usize>,
+        include_dead: Option<bool>,
+    ) -> Result<StoryConnection> {
+        story_list(ctx, StoryList::Job, first, after, offset, include_dead).await
+    }
+
+    /// The most recently submitted polls, newest first.
+    ///
+    /// Polls appear in none of the story lists, so they are found through
+    /// HN Search. At most one page's worth of polls is returned, and only
+    /// the newest 1000 can be reached.
+    async fn recent_polls(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<usize>,
+        offset: Option<usize>,
+    ) -> Result<Vec<Poll>> {
+        let client = ctx.data_unchecked::<HnClient>();
+        let offset = offset.unwrap_or(0).min(MAX_SEARCH_HITS);
+        let limit = limit
+            .unwrap_or(10)
+            .min(PageLimits::get(ctx).max)
+            .min(MAX_SEARCH_HITS - offset);
+        let ids = client.get_recent_poll_ids(offset + limit).await?;
+
+        Ok(load_items(ctx, connection::slice(&ids, offset, limit))
+            .await
+            .into_iter()
+            .filter_map(|item| match item {
+                Item::Poll(poll) => Some(poll),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// The top stories re-ranked with the given strategy.
+    ///
+    /// The first 100 stories of the top list are ranked; `weights` only
+    /// applies to the `CUSTOM` strategy. `limit` defaults to 30 and is
+    /// capped at the server's maximum page size.
+    async fn front_page(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default_with = "RankingStrategy::HnGravity")] strategy: RankingStrategy,
+        weights: Option<RankingWeights>,
+        limit: Option<usize>,
+        offset: Option<usize>,
+    ) -> Result<Vec<Item>> {
+        let client = ctx.data_unchecked::<HnClient>();
+        let weights = weights.unwrap_or_default();
+        let limit = limit.unwrap_or(30).min(PageLimits::get(ctx).max);
+        let ids = client.get_top_stories().await?;
+        let len = ids.len().min(FRONT_PAGE_POOL);
+
+        let now = now();
+        let mut ranked = load_items(ctx, &ids[..len])
+            .await
+            .into_iter()
+            .map(|item| (rank(&item, strategy, weights, now), item))
+            .collect::<Vec<_>>();
+        ranked.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+        Ok(ranked
+            .into_iter()
+            .skip(offset.unwrap_or(0))
+            .take(limit)
+            .map(|(_, item)| item)
+            .collect())
+    }
+
+    /// Highly replied-to comments posted on recent top stories.
+    ///
+    /// Only comments posted within `window` (default `24h`) are
+    /// considered, whenever their story was submitted. Comments are ranked
+    /// by their number of direct replies, weighted by their author's karma
+    /// when `weightByKarma` is set, and ties by id. `limit` defaults to 10 and is capped at
+    /// the server's maximum page size.
+    async fn best_comments(
+        &self,
+        ctx: &Context<'_>,
+        window: Option<Span>,
+        limit: Option<usize>,
+        offset: Option<usize>,
+        weight_by_karma: Option<bool>,
+    ) -> Result<Vec<Comment>> {
+        let client = ctx.data_unchecked::<HnClient>();
+        let loader = ctx.data_unchecked::<ItemDataLoader>();
+        let limit = limit.unwrap_or(10).min(PageLimits::get(ctx).max);
+        let window = window.map_or(DEFAULT_BEST_COMMENTS_WINDOW, |window| window.0.as_secs());
+        let since = now().saturating_sub(window);
+
+        let story_ids = client
+            .get_top_stories()
+            .await?
+            .into_iter()
+            .take(BEST_COMMENTS_STORIES)
+            .collect::<Vec<_>>();
+        let comment_ids = loader
+            .load_many(story_ids)
+            .await
+            .unwrap()
+            .into_values()
+            .filter_map(|item| match item {
+                Item::Story(story) => Some(story.kids),
+                _ => None,
+            })
+            .flatten()
+            .collect::<Vec<_>>();
+        let comments = loader
+            .load_many(comment_ids)
+            .await
+            .unwrap()
+            .into_values()
+            .filter_map(|item| match item {
+                Item::Comment(comment) if comment.time >= since => Some(comment),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        let karma = if weight_by_karma.unwrap_or(false) {
+            let authors = comments
+                .iter()
+                .filter_map(|c| c.by.clone())
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .collect::<Vec<_>>();
+            load_users(ctx, &authors)
+                .await
+                .into_iter()
+                .map(|user| (user.id, user.karma))
+                .collect::<HashMap<_, _>>()
+        } else {
+            HashMap::new()
+        };
+        let rank = |comment: &Comment| {
+            let replies = comment.kids.len() as f64;
+            match comment.by.as_ref().and_then(|by| karma.get(by)) {
+                Some(karma) => replies * (1.0 + f64::from(*karma).log10().max(0.0)),
+                None => replies,
+            }
+        };
+
+        let mut ranked = comments
+            .into_iter()
+            .map(|comment| (rank(&comment), comment))
+            .collect::<Vec<_>>();
+        // The comments were collected from hash maps, so ties need an order
+        // of their own to come back the same on every request.
+        ranked.sort_by(|(a, x), (b, y)| b.total_cmp(a).then(x.id.cmp(&y.id)));
+
+        Ok(ranked
+            .into_iter()
+            .skip(offset.unwrap_or(0))
+            .take(limit)
+            .map(|(_, comment)| comment)
+            .collect())
+    }
+}
+
+/// Resolve a page of the stories in `list`, for `stories` and the fields
+/// named after each list.
+async fn story_list(
+    ctx: &Context<'_>,
+    list: StoryList,
+    first: Option<usize>,
+    after: Option<String>,
+    offset: Option<usize>,
+    include_dead: Option<bool>,
+) -> Result<StoryConnection> {
+    let offset = offset.unwrap_or(0);
+    let include_dead = include_dead.unwrap_or(false);
+    StoryConnection::load(ctx, list, after, offset, first, include_dead).await
+}
+
+/// The number of hits HN Search will page through for one query.
+const MAX_SEARCH_HITS: usize = 1000;
+
+/// Number of top stories ranked by `frontPage`.
+const FRONT_PAGE_POOL: usize = 100;
+
+/// The default window of `bestComments`: one day, in seconds.
+const DEFAULT_BEST_COMMENTS_WINDOW: u64 = 24 * 60 * 60;
+
+/// Number of top stories scanned for `bestComments`.
+const BEST_COMMENTS_STORIES: usize = 30;
+
+/// The number of HN Search hits examined by `canonicalStory`.
+const CANONICAL_SEARCH_HITS: usize = 50;
@@ -0,0 +1,305 @@
+//! The HTTP server, for running the API inside another application.
+//!
+//! The `hn_gql` binary is a thin wrapper around `Server`. Embedders and
+//! tests can start the same routes on their own runtime, hook into
+//! startup and shutdown, and stop the server cleanly through its handle.
+
+use crate::client::{HnClient, ItemLoader, UserLoader};
+use crate::mirror;
+use crate::result::{Error, Result};
+use crate::schema::HnSchema;
+use crate::stats::{
+    BudgetClasses, CountingCache, LoaderStats, UpstreamBudget, BUDGET_CLASS_HEADER, COST_HEADER,
+    LOADER_STATS_HEADER,
+};
+use crate::usage::FieldUsage;
+use async_graphql::dataloader::DataLoader;
+use async_graphql::http::{playground_source, GraphQLPlaygroundConfig};
+use async_graphql::ServerError;
+use async_graphql_warp::{BadRequest, Response};
+use http::StatusCode;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use warp::{http::Response as HttpResponse, Filter, Rejection};
+
+/// Name of the response header echoing the request's operation name.
+static OPERATION_HEADER: &str = "x-graphql-operation";
+
+/// The address served by default.
+pub const DEFAULT_ADDR: ([u8; 4], u16) = ([0, 0, 0, 0], 8000);
+
+type StartupHook = Box<dyn FnOnce(SocketAddr) + Send>;
+type ShutdownHook = Box<dyn FnOnce() + Send>;
+
+/// The HTTP server serving the schema, the Firebase mirror and the admin
+/// routes.
+pub struct Server;
+
+impl Server {
+    /// Start configuring a server for `schema`, making upstream calls with
+    /// `client`.
+    pub fn builder(schema: HnSchema, client: HnClient) -> ServerBuilder {
+        ServerBuilder {
+            schema,
+            client,
+            addr: DEFAULT_ADDR.into(),
+            budgets: BudgetClasses::default(),
+            mirror: true,
+            usage: None,
+            on_startup: Vec::new(),
+            on_shutdown: Vec::new(),
+        }
+    }
+}
+
+/// Configuration of a `Server`, created by `Server::builder`.
+pub struct ServerBuilder {
+    schema: HnSchema,
+    client: HnClient,
+    addr: SocketAddr,
+    budgets: BudgetClasses,
+    mirror: bool,
+    usage: Option<FieldUsage>,
+    on_startup: Vec<StartupHook>,
+    on_shutdown: Vec<ShutdownHook>,
+}
+
+impl ServerBuilder {
+    /// Listen on `addr` instead of `0.0.0.0:8000`. Port 0 picks a free
+    /// port, which startup hooks and `ServerHandle::addr` report.
+    pub fn addr(self, addr: impl Into<SocketAddr>) -> Self {
+        Self {
+            addr: addr.into(),
+            ..self
+        }
+    }
+
+    /// Allow requests of no known budget class at most `budget` upstream
+    /// calls.
+    pub fn upstream_budget(self, budget: usize) -> Self {
+        Self {
+            budgets: self.budgets.clone().default_limit(budget),
+            ..self
+        }
+    }
+
+    /// Pick each request's upstream budget from `budgets` by its
+    /// `x-budget-class` header, replacing the default classes.
+    pub fn budget_classes(self, budgets: BudgetClasses) -> Self {
+        Self { budgets, ..self }
+    }
+
+    /// Serve the `/v0` Firebase mirror, which is on by default. The mirror
+    /// passes upstream documents through as they are, so it should be off
+    /// when the schema redacts fields.
+    pub fn mirror(self, enabled: bool) -> Self {
+        Self {
+            mirror: enabled,
+            ..self
+        }
+    }
+
+    /// Serve the report of `usage` at `/admin/field-usage`. The same
+    /// counters should be registered as an extension of the schema.
+    pub fn field_usage(self, usage: FieldUsage) -> Self {
+        Self {
+            usage: Some(usage),
+            ..self
+        }
+    }
+
+    /// Call `hook` with the bound address once the server listens.
+    pub fn on_startup(mut self, hook: impl FnOnce(SocketAddr) + Send + 'static) -> Self {
+        self.on_startup.push(Box::new(hook));
+        self
+    }
+
+    /// Call `hook` once the server has shut down and finished serving the
+    /// requests in flight.
+    pub fn on_shutdown(mut self, hook: impl FnOnce() + Send + 'static) -> Self {
+        self.on_shutdown.push(Box::new(hook));
+        self
+    }
+
+    /// Bind the address and start serving on the current runtime.
+    ///
+    /// Startup hooks run, in order, before this returns.
+    pub fn start(self) -> Result<ServerHandle> {
+        let (shutdown, stopped) = oneshot::channel();
+        let (addr, serving) =
+            warp::serve(self.routes()).try_bind_with_graceful_shutdown(self.addr, async {
+                let _ = stopped.await;
+            })?;
+        let task = tokio::spawn(serving);
+
+        for hook in self.on_startup {
+            hook(addr);
+        }
+
+        Ok(ServerHandle {
+            addr,
+            shutdown,
+            task,
+            on_shutdown: self.on_shutdown,
+        })
+    }
+
+    /// Assemble the routes served.
+    fn routes(
+        &self,
+    ) -> impl Filter<Extract = impl warp::Reply, Error = Infallible> + Clone + Send + Sync + 'static
+    {
+        let client = self.client.clone();
+        let budgets = self.budgets.clone();
+
+        let mirror_enabled = self.mirror;
+        let mirror = warp::any()
+            .and_then(move || async move {
+                match mirror_enabled {
+                    true => Ok(()),
+                    false => Err(warp::reject::not_found()),
+                }
+            })
+            .untuple_one()
+            .and(mirror::routes(client.clone()));
+
+        let usage = self.usage.clone();
+        let field_usage = warp::path!("admin" / "field-usage")
+            .and(warp::get())
+            .and_then(move || {
+                let report = usage.as_ref().map(FieldUsage::report);
+                async move {
+                    match report {
+                        Some(report) => Ok(warp::reply::json(&report)),
+                        None => Err(warp::reject::not_found()),
+                    }
+                }
+            });
+
+        let graphql_post = async_graphql_warp::graphql(self.schema.clone())
+            .and(warp::header::optional::<String>(LOADER_STATS_HEADER))
+            .and(warp::header::optional::<String>(BUDGET_CLASS_HEADER))
+            .and_then(
+                move |(schema, request), stats_header, class: Option<String>| {
+                    let budget = budgets.limit(class.as_deref());
+                    execute(schema, request, client.clone(), budget, stats_header)
+                },
+            );
+
+        let graphql_playground = warp::path::end().and(warp::get()).map(|| {
+            HttpResponse::builder()
+                .header("content-type", "text/html")
+                .body(playground_source(GraphQLPlaygroundConfig::new("/")))
+        });
+
+        graphql_playground
+            .or(mirror)
+            .or(field_usage)
+            .or(graphql_post)
+            .recover(|err: Rejection| async move {
+                if let Some(BadRequest(err)) = err.find() {
+                    return Ok::<_, Infallible>(warp::reply::with_status(
+                        err.to_string(),
+                        StatusCode::BAD_REQUEST,
+                    ));
+                }
+
+                Ok(warp::reply::with_status(
+                    "INTERNAL_SERVER_ERROR".to_string(),
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                ))
+            })
+    }
+}
+
+/// A running server.
+///
+/// Dropping the handle leaves the server running until the runtime shuts
+/// down, without calling the shutdown hooks.
+pub struct ServerHandle {
+    addr: SocketAddr,
+    shutdown: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+    on_shutdown: Vec<ShutdownHook>,
+}
+
+impl ServerHandle {
+    /// The address the server listens on.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Stop accepting connections, wait for the requests in flight, and
+    /// run the shutdown hooks in order.
+    pub async fn shutdown(self) {
+        let _ = self.shutdown.send(());
+        let _ = self.task.await;
+        for hook in self.on_shutdown {
+            hook();
+        }
+    }
+}
+
+/// Execute a GraphQL request within an upstream call budget, attaching
+/// loader statistics when asked for.
+///
+/// The operation name and the number of upstream calls made are also
+/// returned as headers, so that gateways can act on them without parsing
+/// the body.
+async fn execute(
+    schema: HnSchema,
+    request: async_graphql::Request,
+    client: HnClient,
+    budget: usize,
+    stats_header: Option<String>,
+) -> std::result::Result<Response, Infallible> {
+    // Give the request its own client and loader so the budget and
+    // counters only see the work done on its behalf.
+    let budget = Arc::new(UpstreamBudget::new(budget));
+    let client = client.with_budget(budget.clone());
+    let stats = stats_header.map(|_| Arc::new(LoaderStats::default()));
+    let client = match &stats {
+        Some(stats) => client.with_stats(stats.clone()),
+        None => client,
+    };
+
+    let operation = request.operation_name.clone();
+    let request = request
+        .data(budget.clone())
+        .data(client.clone())
+        .data(DataLoader::with_cache(
+            ItemLoader {
+                client: client.clone(),
+            },
+            CountingCache::new(stats.clone()),
+        ))
+        .data(DataLoader::with_cache(
+            UserLoader { client },
+            CountingCache::new(stats.clone()),
+        ));
+    let mut response = schema.execute(request).await;
+
+    if let Some(stats) = stats {
+        response = response.extension("loaderStats", stats.to_value());
+    }
+    if budget.exceeded() {
+        // Loaders leave out the items they couldn't fetch, so say why
+        // they are missing.
+        response.errors.push(ServerError::new(
+            Error::UpstreamBudgetExceeded(budget.limit()).to_string(),
+            None,
+        ));
+        response = response.extension("upstreamBudgetExceeded", true.into());
+    }
+    if let Some(operation) = operation {
+        response.http_headers.insert(OPERATION_HEADER, operation);
+    }
+    response
+        .http_headers
+        .insert(COST_HEADER, budget.used().to_string());
+
+    Ok(Response::from(response))
+}
@@ -0,0 +1,298 @@
+//! Per-request accounting of upstream and dataloader work.
+
+use crate::result::{Error, Result};
+use async_graphql::dataloader::{CacheFactory, CacheStorage};
+use async_graphql::extensions::{Extension, ExtensionContext, ExtensionFactory, NextValidation};
+use async_graphql::{value, ServerError, ValidationResult, Value};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Name of the request header that opts a request into loader statistics.
+pub static LOADER_STATS_HEADER: &str = "x-loader-stats";
+
+/// Counters describing how much upstream work a single request consumed.
+#[derive(Debug, Default)]
+pub struct LoaderStats {
+    upstream_calls: AtomicUsize,
+    batches: AtomicUsize,
+    hydrated_items: AtomicUsize,
+    cache_hits: AtomicUsize,
+}
+
+impl LoaderStats {
+    /// Record a single HTTP call to the Firebase API.
+    pub fn record_upstream_call(&self) {
+        self.upstream_calls.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a dataloader batch that hydrated `items` items.
+    pub fn record_batch(&self, items: usize) {
+        self.batches.fetch_add(1, Ordering::Relaxed);
+        self.hydrated_items.fetch_add(items, Ordering::Relaxed);
+    }
+
+    /// Record a dataloader lookup answered from the request's cache.
+    pub fn record_cache_hit(&self) {
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Render the counters as a response extension value.
+    pub fn to_value(&self) -> Value {
+        value!({
+            "upstreamCalls": self.upstream_calls.load(Ordering::Relaxed),
+            "batches": self.batches.load(Ordering::Relaxed),
+            "hydratedItems": self.hydrated_items.load(Ordering::Relaxed),
+            "cacheHits": self.cache_hits.load(Ordering::Relaxed),
+        })
+    }
+}
+
+/// A dataloader cache that counts its hits into a request's
+/// `LoaderStats`.
+///
+/// Entries live as long as the loader, so the cache belongs on loaders
+/// created for a single request.
+#[derive(Debug, Clone, Default)]
+pub struct CountingCache {
+    stats: Option<Arc<LoaderStats>>,
+}
+
+impl CountingCache {
+    /// Create a cache counting its hits into `stats`, if given.
+    pub fn new(stats: Option<Arc<LoaderStats>>) -> Self {
+        Self { stats }
+    }
+}
+
+impl CacheFactory for CountingCache {
+    fn create<K, V>(&self) -> Box<dyn CacheStorage<Key = K, Value = V>>
+    where
+        K: Send + Sync + Clone + Eq + Hash + 'static,
+        V: Send + Sync + Clone + 'static,
+    {
+        Box::new(CountingStorage {
+            values: HashMap::new(),
+            stats: self.stats.clone(),
+        })
+    }
+}
+
+/// The storage behind `CountingCache`.
+struct CountingStorage<K, V> {
+    values: HashMap<K, V>,
+    stats: Option<Arc<LoaderStats>>,
+}
+
+impl<K, V> CacheStorage for CountingStorage<K, V>
+where
+    K: Send + Sync + Clone + Eq + Hash + 'static,
+    V: Send + Sync + Clone + 'static,
+{
+    type Key = K;
+    type Value = V;
+
+    fn get(&mut self, key: &K) -> Option<&V> {
+        let value = self.values.get(key);
+        if let (Some(_), Some(stats)) = (value, &self.stats) {
+            stats.record_cache_hit();
+        }
+        value
+    }
+
+    fn insert(&mut self, key: Cow<'_, K>, value: Cow<'_, V>) {
+        self.values.insert(key.into_owned(), value.into_owned());
+    }
+
+    fn remove(&mut self, key: &K) {
+        self.values.remove(key);
+    }
+
+    fn clear(&mut self) {
+        self.values.clear();
+    }
+}
+
+/// Name of the response header reporting how many upstream calls a
+/// request made.
+pub static COST_HEADER: &str = "x-cost";
+
+/// Name of the request header naming the request's budget class.
+pub static BUDGET_CLASS_HEADER: &str = "x-budget-class";
+
+/// The default number of upstream calls a single request may make.
+pub const DEFAULT_UPSTREAM_BUDGET: usize = 1000;
+
+/// The budget classes configured unless `HN_BUDGET_CLASSES` says
+/// otherwise.
+pub const DEFAULT_BUDGET_CLASSES: &[(&str, usize)] = &[("interactive", 200), ("batch", 5000)];
+
+/// Upstream call budgets by request class, picked by the
+/// `x-budget-class` header. Requests naming no known class get the
+/// default budget.
+#[derive(Debug, Clone)]
+pub struct BudgetClasses {
+    default: usize,
+    classes: HashMap<String, usize>,
+}
+
+impl Default for BudgetClasses {
+    fn default() -> Self {
+        DEFAULT_BUDGET_CLASSES.iter().fold(
+            Self::new(DEFAULT_UPSTREAM_BUDGET),
+            |classes, (name, limit)| classes.class(name, *limit),
+        )
+    }
+}
+
+impl BudgetClasses {
+    /// Create a set of classes, none yet, giving every request `default`
+    /// upstream calls.
+    pub fn new(default: usize) -> Self {
+        Self {
+            default,
+            classes: HashMap::new(),
+        }
+    }
+
+    /// Give requests of class `name` `limit` upstream calls.
+    pub fn class(mut self, name: &str, limit: usize) -> Self {
+        self.classes.insert(name.to_string(), limit);
+        self
+    }
+
+    /// Give requests of no known class `limit` upstream calls.
+    pub fn default_limit(self, limit: usize) -> Self {
+        Self {
+            default: limit,
+            ..self
+        }
+    }
+
+    /// Read the default budget from `HN_UPSTREAM_BUDGET` and the classes
+    /// from `HN_BUDGET_CLASSES`, a comma-separated list of `name=limit`
+    /// pairs, keeping the defaults for whatever is unset.
+    pub fn from_env() -> Result<Self> {
+        let mut classes = Self::default();
+        if let Ok(limit) = std::env::var("HN_UPSTREAM_BUDGET") {
+            let limit = limit
+                .trim()
+                .parse()
+                .map_err(|_| Error::InvalidBudgetClass(limit.clone()))?;
+            classes = classes.default_limit(limit);
+        }
+        if let Ok(spec) = std::env::var("HN_BUDGET_CLASSES") {
+            classes.classes.clear();
+            for class in spec.split(',').map(str::trim).filter(|c| !c.is_empty()) {
+                let (name, limit) = parse_class(class)
+                    .ok_or_else(|| Error::InvalidBudgetClass(class.to_string()))?;
+                classes = classes.class(name, limit);
+            }
+        }
+        Ok(classes)
+    }
+
+    /// Return the budget of a request of class `class`.
+    pub fn limit(&self, class: Option<&str>) -> usize {
+        class
+            .and_then(|class| self.classes.get(class.trim()))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+fn parse_class(class: &str) -> Option<(&str, usize)> {
+    let mut parts = class.splitn(2, '=');
+    let name = parts.next()?.trim();
+    let limit = parts.next()?.trim().parse().ok()?;
+    match name.is_empty() {
+        true => None,
+        false => Some((name, limit)),
+    }
+}
+
+/// A cap on the number of upstream calls a single request may make.
+#[derive(Debug)]
+pub struct UpstreamBudget {
+    limit: usize,
+    used: AtomicUsize,
+    exceeded: AtomicBool,
+}
+
+impl UpstreamBudget {
+    /// Create a budget allowing `limit` upstream calls.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            used: AtomicUsize::new(0),
+            exceeded: AtomicBool::new(false),
+        }
+    }
+
+    /// Return the number of upstream calls the budget allows.
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Take one call from the budget, failing once it is used up.
+    pub fn acquire(&self) -> Result<()> {
+        if self.used.fetch_add(1, Ordering::Relaxed) < self.limit {
+            return Ok(());
+        }
+        self.exceeded.store(true, Ordering::Relaxed);
+        Err(Error::UpstreamBudgetExceeded(self.limit))
+    }
+
+    /// Return the number of calls taken from the budget, not counting
+    /// refused ones.
+    pub fn used(&self) -> usize {
+        self.used.load(Ordering::Relaxed).min(self.limit)
+    }
+
+    /// Return whether any call was refused because the budget ran out.
+    pub fn exceeded(&self) -> bool {
+        self.exceeded.load(Ordering::Relaxed)
+    }
+}
+
+/// A schema extension rejecting, before they run, queries whose
+/// complexity exceeds the request's upstream budget.
+///
+/// Every field counts towards complexity, so this is a cheap first check
+/// that turns away queries too large to possibly fit; the budget itself
+/// still caps the upstream calls actually made. Requests without an
+/// `UpstreamBudget` in their data aren't checked.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BudgetGuard;
+
+impl ExtensionFactory for BudgetGuard {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(BudgetGuard)
+    }
+}
+
+#[async_trait::async_trait]
+impl Extension for BudgetGuard {
+    async fn validation(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        next: NextValidation<'_>,
+    ) -> std::result::Result<ValidationResult, Vec<ServerError>> {
+        let result = next.run(ctx).await?;
+        if let Some(budget) = ctx.data_opt::<Arc<UpstreamBudget>>() {
+            if result.complexity > budget.limit() {
+                return Err(vec![ServerError::new(
+                    format!(
+                        "query complexity {} exceeds the upstream call budget of {} calls",
+                        result.complexity,
+                        budget.limit()
+                    ),
+                    None,
+                )]);
+            }
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,476 @@
+//! Processing of the HTML fragments used in item and profile text.
+//!
+//! HN only emits a small subset of HTML: `<p>` separated paragraphs,
+//! links, italics and `<pre><code>` blocks, plus character references.
+//! Every conversion of that HTML lives here, behind the `Processor` trait,
+//! so resolvers and parsers share one tokenizer instead of each stripping
+//! tags their own way.
+
+use futures::future::join_all;
+
+/// A conversion of an HTML fragment into another representation.
+pub trait Processor {
+    /// The representation produced.
+    type Output;
+
+    /// Process the HTML fragment `html`.
+    fn process(&self, html: &str) -> Self::Output;
+}
+
+/// The number of fragments converted by each blocking task in
+/// `process_all`.
+const BATCH_SIZE: usize = 256;
+
+/// Convert many HTML fragments with `processor` on tokio's blocking pool,
+/// returning the outputs in input order.
+///
+/// Fragments are converted in parallel batches. Use this for bulk work
+/// such as exports, where converting thousands of fragments on the async
+/// workers would stall other requests.
+pub async fn process_all<P>(processor: P, fragments: Vec<String>) -> Vec<P::Output>
+where
+    P: Processor + Clone + Send + 'static,
+    P::Output: Send + 'static,
+{
+    let len = fragments.len();
+    let mut fragments = fragments.into_iter();
+    let mut tasks = Vec::new();
+    loop {
+        let batch = fragments.by_ref().take(BATCH_SIZE).collect::<Vec<_>>();
+        if batch.is_empty() {
+            break;
+        }
+        let processor = processor.clone();
+        tasks.push(tokio::task::spawn_blocking(move || {
+            batch
+                .iter()
+                .map(|html| processor.process(html))
+                .collect::<Vec<_>>()
+        }));
+    }
+
+    let mut outputs = Vec::with_capacity(len);
+    for batch in join_all(tasks).await {
+        outputs.extend(batch.unwrap_or_else(|err| std::panic::resume_unwind(err.into_panic())));
+    }
+    outputs
+}
+
+/// Converts HTML to plain text.
+///
+/// Tags are removed, paragraphs become blank-line separated, and
+/// character references are decoded.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PlainText;
+
+impl Processor for PlainText {
+    type Output = String;
+
+    fn process(&self, html: &str) -> String {
+        let mut text = String::with_capacity(html.len());
+        for token in tokens(html) {
+            match token {
+                Token::Text(s) => text.push_str(&decode_entities(s)),
+                Token::Open(tag) if tag.name == "p" => text.push_str("\n\n"),
+                _ => {}
+            }
+        }
+        text.trim().to_string()
+    }
+}
+
+/// Extracts the targets of the links in HTML, in order.
+///
+/// Character references in the targets are decoded.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Links;
+
+impl Processor for Links {
+    type Output = Vec<String>;
+
+    fn process(&self, html: &str) -> Vec<String> {
+        tokens(html)
+            .filter_map(|token| match token {
+                Token::Open(tag) if tag.name == "a" => tag.attribute("href"),
+                _ => None,
+            })
+            .map(decode_entities)
+            .collect()
+    }
+}
+
+/// Converts HTML to Markdown.
+///
+/// Italics become `*emphasis*`, links become `<url>` autolinks, and code
+/// blocks become fenced blocks. Characters that Markdown or HTML would
+/// interpret are escaped, except for the `>` starting a quoted paragraph.
+/// Links whose target is not an `http` or `https` URL are replaced by
+/// their text.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Markdown;
+
+impl Processor for Markdown {
+    type Output = String;
+
+    fn process(&self, html: &str) -> String {
+        let mut md = String::with_capacity(html.len());
+        let mut in_link = false;
+        let mut code_start = None;
+        for token in tokens(html) {
+            match token {
+                Token::Text(_) if in_link => {}
+                Token::Text(s) if code_start.is_some() => md.push_str(&decode_entities(s)),
+                Token::Text(s) => {
+                    let text = decode_entities(s);
+                    let at_paragraph_start = md.is_empty() || md.ends_with("\n\n");
+                    let text = match text.strip_prefix('>').filter(|_| at_paragraph_start) {
+                        Some(quoted) => {
+                            md.push('>');
+                            quoted.to_string()
+                        }
+                        None => text,
+                    };
+                    md.push_str(&escape_markdown(&text));
+                }
+                Token::Open(tag) => match tag.name.as_str() {
+                    "p" => paragraph_break(&mut md),
+                    "i" => md.push('*'),
+                    "pre" => {
+                        paragraph_break(&mut md);
+                        code_start = Some(md.len());
+                    }
+                    "a" => {
+                        let href = tag.attribute("href").map(decode_entities);
+                        if let Some(link) = href.as_deref().and_then(autolink) {
+                            md.push_str(&link);
+                            in_link = true;
+                        }
+                    }
+                    _ => {}
+                },
+                Token::Close(name) => match name.as_str() {
+                    "i" => md.push('*'),
+                    "pre" => {
+                        if let Some(start) = code_start.take() {
+                            let code = md.split_off(start);
+                            fence_code(&mut md, code.trim_end_matches('\n'));
+                            paragraph_break(&mut md);
+                        }
+                    }
+                    "a" => in_link = false,
+                    _ => {}
+                },
+            }
+        }
+        md.trim().to_string()
+    }
+}
+
+/// End the current paragraph of `md` with exactly one blank line.
+fn paragraph_break(md: &mut String) {
+    md.truncate(md.trim_end_matches('\n').len());
+    md.push_str("\n\n");
+}
+
+/// Append `code` to `md` as a fenced block, with a fence longer than any
+/// run of backticks inside it.
+fn fence_code(md: &mut String, code: &str) {
+    let longest_run = code.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    md.push_str(&format!("{}\n{}\n{}", fence, code, fence));
+}
+
+/// Return `href` as a Markdown autolink, or `None` if it is not an
+/// `http` or `https` URL.
+fn autolink(href: &str) -> Option<String> {
+    let lower = href.to_ascii_lowercase();
+    if !is_safe_url(href) || !(lower.starts_with("http://") || lower.starts_with("https://")) {
+        return None;
+    }
+    let mut link = String::with_capacity(href.len() + 2);
+    link.push('<');
+    for c in href.chars() {
+        match c {
+            '<' => link.push_str("%3C"),
+            '>' => link.push_str("%3E"),
+            c if c.is_whitespace() || c.is_control() => {
+                let mut buf = [0; 4];
+                for b in c.encode_utf8(&mut buf).bytes() {
+                    link.push_str(&format!("%{:02X}", b));
+                }
+            }
+            c => link.push(c),
+        }
+    }
+    link.push('>');
+    Some(link)
+}
+
+/// Backslash-escape the characters of `s` that Markdown or HTML would
+/// interpret.
+fn escape_markdown(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "\\`*_[]<>&#~|".contains(c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Extracts the paragraphs quoted from other comments.
+///
+/// HN has no quote markup; by convention quoted paragraphs start with
+/// `>`. The marker is removed from the returned quotes.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Quotes;
+
+impl Processor for Quotes {
+    type Output = Vec<String>;
+
+    fn process(&self, html: &str) -> Vec<String> {
+        PlainText
+            .process(html)
+            .split("\n\n")
+            .filter_map(|paragraph| paragraph.trim().strip_prefix('>'))
+            .map(|quote| quote.trim().to_string())
+            .filter(|quote| !quote.is_empty())
+            .collect()
+    }
+}
+
+/// Reduces HTML to the subset HN emits.
+///
+/// Unknown tags and all attributes except link targets are dropped, so
+/// the output is safe to embed in a page.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sanitize;
+
+/// The tags kept by `Sanitize`.
+static ALLOWED_TAGS: &[&str] = &["p", "i", "a", "pre", "code"];
+
+impl Processor for Sanitize {
+    type Output = String;
+
+    fn process(&self, html: &str) -> String {
+        let mut out = String::with_capacity(html.len());
+        for token in tokens(html) {
+            match token {
+                Token::Text(s) => out.push_str(&escape(&decode_entities(s))),
+                Token::Open(tag) if ALLOWED_TAGS.contains(&tag.name.as_str()) => {
+                    match tag.attribute("href").filter(|_| tag.name == "a") {
+                        Some(href) => {
+                            let href = decode_entities(href);
+                            if is_safe_url(&href) {
+                                out.push_str(&format!(
+                                    "<a href=\"{}\" rel=\"nofollow\">",
+                                    escape(&href)
+                                ));
+                            } else {
+                                out.push_str("<a>");
+                            }
+                        }
+                        None => out.push_str(&format!("<{}>", tag.name)),
+                    }
+                }
+                Token::Close(name) if ALLOWED_TAGS.contains(&name.as_str()) => {
+                    out.push_str(&format!("</{}>", name))
+                }
+                _ => {}
+            }
+        }
+        out
+    }
+}
+
+/// A piece of an HTML fragment.
+enum Token<'a> {
+    /// Text between tags, with character references still encoded.
+    Text(&'a str),
+    /// An opening tag.
+    Open(Tag<'a>),
+    /// A closing tag, by lowercase name.
+    Close(String),
+}
+
+/// An opening tag.
+struct Tag<'a> {
+    /// The tag's lowercase name.
+    name: String,
+    /// Everything after the name, as written.
+    attributes: &'a str,
+}
+
+impl<'a> Tag<'a> {
+    /// Return the value of the attribute `name`, which may be quoted with
+    /// either kind of quote or unquoted. `None` if the tag has no such
+    /// attribute, or only names it without a value.
+    fn attribute(&self, name: &str) -> Option<&'a str> {
+        let mut rest = self.attributes;
+        loop {
+            // Each attribute starts after whitespace, or at the start.
+            rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+            if rest.is_empty() {
+                return None;
+            }
+            let len = rest
+                .find(|c: char| c.is_whitespace() || c == '=' || c == '/')
+                .unwrap_or(rest.len())
+                .max(1);
+            let found = &rest[..len];
+            rest = rest[len..].trim_start();
+
+            let value = match rest.strip_prefix('=') {
+                Some(value) => value.trim_start(),
+                None => continue,
+            };
+            let (value, after) = match value.chars().next() {
+                Some(quote @ '"') | Some(quote @ '\'') => {
+                    let value = &value[1..];
+                    let len = value.find(quote).unwrap_or(value.len());
+                    (&value[..len], value.get(len + 1..).unwrap_or(""))
+                }
+                _ => {
+                    let len = value.find(char::is_whitespace).unwrap_or(value.len());
+                    (&value[..len], &value[len..])
+                }
+            };
+            if found.eq_ignore_ascii_case(name) {
+                return Some(value);
+            }
+            rest = after;
+        }
+    }
+}
+
+/// Split `html` into text and tags. An unterminated tag is kept as text.
+fn tokens(html: &str) -> impl Iterator<Item = Token<'_>> {
+    let mut rest = html;
+    let mut pending = None;
+
+    std::iter::from_fn(move || {
+        if let Some(tag) = pending.take() {
+            return Some(tag);
+        }
+        loop {
+            if rest.is_empty() {
+                return None;
+            }
+            let start = match rest.find('<') {
+                Some(start) => start,
+                None => return Some(Token::Text(std::mem::take(&mut rest))),
+            };
+            let end = match rest[start..].find('>') {
+                Some(end) => start + end,
+                None => return Some(Token::Text(std::mem::take(&mut rest))),
+            };
+
+            let text = &rest[..start];
+            let tag = parse_tag(&rest[start + 1..end]);
+            rest = &rest[end + 1..];
+            match (text.is_empty(), tag) {
+                (true, Some(tag)) => return Some(tag),
+                (true, None) => continue,
+                (false, tag) => {
+                    pending = tag;
+                    return Some(Token::Text(text));
+                }
+            }
+        }
+    })
+}
+
+/// Parse the inside of a tag, returning `None` for comments and the like.
+fn parse_tag(inner: &str) -> Option<Token<'_>> {
+    let inner = inner.trim();
+    let (closing, inner) = match inner.strip_prefix('/') {
+        Some(inner) => (true, inner.trim_start()),
+        None => (false, inner),
+    };
+    let len = inner
+        .find(|c: char| !c.is_ascii_alphanumeric())
+        .unwrap_or(inner.len());
+    if len == 0 {
+        return None;
+    }
+    let name = inner[..len].to_ascii_lowercase();
+
+    Some(if closing {
+        Token::Close(name)
+    } else {
+        Token::Open(Tag {
+            name,
+            attributes: &inner[len..],
+        })
+    })
+}
+
+/// Return whether `url` may be used as a link target.
+fn is_safe_url(url: &str) -> bool {
+    let lower = url.trim_start().to_ascii_lowercase();
+    lower.starts_with("http://") || lower.starts_with("https://") || !lower.contains(':')
+}
+
+/// Escape the characters that are special in HTML text and attributes.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Decode named and numeric character references.
+pub fn decode_entities(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let decoded = rest
+            .find(';')
+            .filter(|end| *end <= 10)
+            .and_then(|end| decode_entity(&rest[1..end]).map(|c| (c, end)));
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+
+    out
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    let code = if let Some(hex) = name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+        u32::from_str_radix(hex, 16).ok()?
+    } else if let Some(dec) = name.strip_prefix('#') {
+        dec.parse().ok()?
+    } else {
+        return match name {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some('\u{a0}'),
+            _ => None,
+        };
+    };
+    char::from_u32(code)
+}
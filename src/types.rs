@@ -1,18 +1,205 @@
 //! Item types returned by the API.
 
-use crate::client::{HnClient, ItemLoader};
-use crate::result::Result;
-use async_graphql::{dataloader::DataLoader, ComplexObject, Context, Interface, SimpleObject};
+use crate::client::{HnClient, ItemDataLoader, UserDataLoader};
+use crate::collapse::CollapseRules;
+use crate::connection::{self, ItemEdge, PageLimits};
+use crate::node;
+use crate::result::{Error, Result};
+use crate::textproc::{Links, Markdown, PlainText, Processor, Quotes};
+use crate::url_utils;
+use async_graphql::{
+    connection::PageInfo, ComplexObject, Context, Description, Enum, InputValueError,
+    InputValueResult, Interface, Object, Scalar, ScalarType, SimpleObject, Value, ID,
+};
 use serde::Deserialize;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The unique id of an item.
+///
+/// Serialized as a decimal string so that ids beyond 2^53 survive JSON
+/// parsers that read numbers as doubles. Integers are accepted as input.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Description)]
+#[serde(transparent)]
+pub struct ItemId(pub u64);
+
+#[Scalar(use_type_description)]
+impl ScalarType for ItemId {
+    fn parse(value: Value) -> InputValueResult<Self> {
+        let id = match &value {
+            Value::String(s) => s.parse().ok(),
+            Value::Number(n) => n.as_u64(),
+            _ => None,
+        };
+        id.map(ItemId)
+            .ok_or_else(|| InputValueError::expected_type(value))
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.0.to_string())
+    }
+}
+
+impl fmt::Display for ItemId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// A user's unique username. Case-sensitive, and made of ASCII letters,
+/// digits, `_` and `-`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Description)]
+#[serde(transparent)]
+pub struct Username(pub String);
+
+#[Scalar(use_type_description)]
+impl ScalarType for Username {
+    fn parse(value: Value) -> InputValueResult<Self> {
+        match value {
+            Value::String(s) if Username::is_valid(&s) => Ok(Username(s)),
+            value => Err(InputValueError::expected_type(value)),
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.0.clone())
+    }
+}
+
+impl Username {
+    /// Return whether `name` has the shape of a username.
+    pub(crate) fn is_valid(name: &str) -> bool {
+        !name.is_empty()
+            && name
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+    }
+
+    /// Return the username as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Username {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// The last second of year 9999, the latest time RFC 3339 can express.
+const MAX_RFC3339_SECS: u64 = 253_402_300_799;
+
+/// A point in time, in Unix Time.
+///
+/// Serialized as an RFC 3339 timestamp in UTC, for example
+/// `2007-04-04T19:16:40Z`. Such timestamps and Unix Time integers are
+/// accepted as input.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Description)]
+pub struct DateTime(pub u64);
+
+#[Scalar(use_type_description)]
+impl ScalarType for DateTime {
+    fn parse(value: Value) -> InputValueResult<Self> {
+        let time = match &value {
+            Value::String(s) => humantime::parse_rfc3339(s)
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs()),
+            Value::Number(n) => n.as_u64(),
+            _ => None,
+        };
+        time.map(DateTime)
+            .ok_or_else(|| InputValueError::expected_type(value))
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.to_string())
+    }
+}
+
+impl fmt::Display for DateTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Times past the end of year 9999 have no RFC 3339 form, so they
+        // are printed as the latest time that does.
+        let secs = self.0.min(MAX_RFC3339_SECS);
+        let time = UNIX_EPOCH
+            .checked_add(Duration::from_secs(secs))
+            .expect("the latest RFC 3339 time fits in a SystemTime");
+        humantime::format_rfc3339_seconds(time).fmt(f)
+    }
+}
+
+/// A length of time, with a resolution of one second.
+///
+/// Serialized like `1h 30m`. Such strings, for example `24h`, `90min` or
+/// `2days`, and integer numbers of seconds are accepted as input.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Description)]
+pub struct Span(pub Duration);
+
+#[Scalar(name = "Duration", use_type_description)]
+impl ScalarType for Span {
+    fn parse(value: Value) -> InputValueResult<Self> {
+        let span = match &value {
+            Value::String(s) => humantime::parse_duration(s).ok(),
+            Value::Number(n) => n.as_u64().map(Duration::from_secs),
+            _ => None,
+        };
+        span.map(|span| Span(Duration::from_secs(span.as_secs())))
+            .ok_or_else(|| InputValueError::expected_type(value))
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(humantime::format_duration(self.0).to_string())
+    }
+}
 
 /// An API item, for example a story or a comment.
+///
+/// Items of an unknown type, or that fail to decode as their type, still
+/// deserialize as `Unknown` as long as they have an id.
+#[allow(clippy::duplicated_attributes)]
 #[derive(Debug, Clone, Deserialize, Interface)]
-#[serde(tag = "type")]
-#[serde(rename_all = "lowercase")]
+#[serde(try_from = "serde_json::Value")]
 #[graphql(
-    field(name = "id", type = "&u32"),
+    field(name = "id", type = "&ItemId"),
     field(name = "title", type = "Option<&str>"),
-    field(name = "author", type = "Option<&str>")
+    field(name = "author", type = "Option<&Username>"),
+    field(name = "item_type", type = "ItemType", desc = "The kind of the item."),
+    field(
+        name = "time",
+        type = "&u64",
+        desc = "Creation date of the item, in Unix Time."
+    ),
+    field(
+        name = "created_at",
+        type = "DateTime",
+        desc = "Creation date of the item."
+    ),
+    field(
+        name = "age",
+        type = "String",
+        desc = "The time since the item was posted, as of the request. `format` defaults to `RELATIVE`.",
+        arg(name = "format", type = "Option<AgeFormat>")
+    ),
+    field(
+        name = "score",
+        type = "Option<&u32>",
+        desc = "The item's score. `null` for comments."
+    ),
+    field(
+        name = "url",
+        type = "&Option<String>",
+        desc = "The item's URL. Only stories and jobs have one."
+    ),
+    field(
+        name = "kids",
+        type = "&Vec<ItemId>",
+        desc = "The ids of the item's replies, in ranked display order. Always empty for jobs and poll options."
+    )
 )]
 pub enum Item {
     /// A story.
@@ -25,6 +212,227 @@ pub enum Item {
     Poll(Poll),
     /// A poll option belonging to a poll.
     Pollopt(Pollopt),
+    /// An item that could not be decoded as any of the other variants.
+    Unknown(UnknownItem),
+}
+
+/// The items with a known `type` tag, as decoded by `Item`.
+#[derive(Deserialize)]
+#[serde(tag = "type")]
+#[serde(rename_all = "lowercase")]
+enum KnownItem {
+    Story(Story),
+    Comment(Comment),
+    Job(Job),
+    Poll(Poll),
+    Pollopt(Pollopt),
+}
+
+impl TryFrom<serde_json::Value> for Item {
+    type Error = serde_json::Error;
+
+    fn try_from(value: serde_json::Value) -> std::result::Result<Self, Self::Error> {
+        let err = match KnownItem::deserialize(&value) {
+            Ok(KnownItem::Story(story)) => return Ok(Item::Story(story)),
+            Ok(KnownItem::Comment(comment)) => return Ok(Item::Comment(comment)),
+            Ok(KnownItem::Job(job)) => return Ok(Item::Job(job)),
+            Ok(KnownItem::Poll(poll)) => return Ok(Item::Poll(poll)),
+            Ok(KnownItem::Pollopt(pollopt)) => return Ok(Item::Pollopt(pollopt)),
+            Err(err) => err,
+        };
+        UnknownItem::from_raw(value).map(Item::Unknown).ok_or(err)
+    }
+}
+
+/// The kind of an item.
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ItemType {
+    /// A story.
+    Story,
+    /// A comment.
+    Comment,
+    /// A job.
+    Job,
+    /// A poll.
+    Poll,
+    /// A poll option.
+    Pollopt,
+    /// An item that could not be decoded.
+    Unknown,
+}
+
+impl Item {
+    /// Return the kind of the item.
+    pub fn kind(&self) -> ItemType {
+        match self {
+            Item::Story(_) => ItemType::Story,
+            Item::Comment(_) => ItemType::Comment,
+            Item::Job(_) => ItemType::Job,
+            Item::Poll(_) => ItemType::Poll,
+            Item::Pollopt(_) => ItemType::Pollopt,
+            Item::Unknown(_) => ItemType::Unknown,
+        }
+    }
+
+    /// Return whether the item is neither deleted nor dead.
+    pub fn is_live(&self) -> bool {
+        let (deleted, dead) = match self {
+            Item::Story(story) => (story.deleted, story.dead),
+            Item::Comment(comment) => (comment.deleted, comment.dead),
+            Item::Job(job) => (job.deleted, job.dead),
+            Item::Poll(poll) => (poll.deleted, poll.dead),
+            Item::Pollopt(pollopt) => (pollopt.deleted, pollopt.dead),
+            Item::Unknown(unknown) => (unknown.deleted, unknown.dead),
+        };
+        !deleted && !dead
+    }
+
+    /// Return the item's HTML text, if it has any.
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            Item::Story(story) => story.text.as_deref(),
+            Item::Comment(comment) => Some(&comment.text),
+            Item::Job(job) => job.text.as_deref(),
+            Item::Poll(poll) => poll.text.as_deref(),
+            Item::Pollopt(pollopt) => pollopt.text.as_deref(),
+            Item::Unknown(_) => None,
+        }
+    }
+
+    /// Record that the item was fetched at `time`.
+    pub(crate) fn set_fetched_at(&mut self, time: u64) {
+        match self {
+            Item::Story(story) => story.fetched_at = time,
+            Item::Comment(comment) => comment.fetched_at = time,
+            Item::Job(job) => job.fetched_at = time,
+            Item::Poll(poll) => poll.fetched_at = time,
+            Item::Pollopt(pollopt) => pollopt.fetched_at = time,
+            Item::Unknown(unknown) => unknown.fetched_at = time,
+        }
+    }
+}
+
+/// The replies of items that can't have any.
+static NO_KIDS: Vec<ItemId> = Vec::new();
+
+/// The URL of items that can't have one.
+static NO_URL: Option<String> = None;
+
+/// Load `ids` through the item loader, preserving their order.
+///
+/// Ids that fail to load are skipped.
+pub(crate) async fn load_items(ctx: &Context<'_>, ids: &[ItemId]) -> Vec<Item> {
+    let mut items = ctx
+        .data_unchecked::<ItemDataLoader>()
+        .load_many(ids.iter().copied())
+        .await
+        .unwrap();
+
+    ids.iter().filter_map(|id| items.remove(id)).collect()
+}
+
+/// Load the user `id` through the user loader, or return `None` if it
+/// fails to load.
+pub(crate) async fn load_user(ctx: &Context<'_>, id: &Username) -> Option<User> {
+    ctx.data_unchecked::<UserDataLoader>()
+        .load_one(id.clone())
+        .await
+        .unwrap()
+}
+
+/// Load `ids` through the user loader, preserving their order.
+///
+/// Users that fail to load are skipped.
+pub(crate) async fn load_users(ctx: &Context<'_>, ids: &[Username]) -> Vec<User> {
+    let mut users = ctx
+        .data_unchecked::<UserDataLoader>()
+        .load_many(ids.iter().cloned())
+        .await
+        .unwrap();
+
+    ids.iter().filter_map(|id| users.remove(id)).collect()
+}
+
+/// The maximum number of comments visited by a single tree walk.
+pub const MAX_TREE_NODES: usize = 2000;
+
+/// The comments found by `walk_comments`.
+struct CommentWalk {
+    /// The comments loaded, level by level.
+    comments: Vec<Comment>,
+    /// Whether a limit left replies unvisited.
+    truncated: bool,
+}
+
+/// Load the comment trees below `roots` breadth-first, one dataloader
+/// batch per level, visiting at most `MAX_TREE_NODES` comments and
+/// `depth` levels, and only the first `breadth` replies to each comment.
+///
+/// Comments are annotated with their depth below `roots`.
+async fn walk_comments(
+    ctx: &Context<'_>,
+    roots: &[ItemId],
+    depth: usize,
+    breadth: usize,
+) -> CommentWalk {
+    let mut comments = Vec::new();
+    let mut truncated = roots.len() > breadth;
+    let mut level = roots.iter().take(breadth).copied().collect::<Vec<_>>();
+
+    for depth in 0..depth {
+        if level.is_empty() || comments.len() >= MAX_TREE_NODES {
+            break;
+        }
+        if level.len() > MAX_TREE_NODES - comments.len() {
+            level.truncate(MAX_TREE_NODES - comments.len());
+            truncated = true;
+        }
+        let mut next = Vec::new();
+        for item in load_items(ctx, &level).await {
+            if let Item::Comment(mut comment) = item {
+                truncated |= comment.kids.len() > breadth;
+                next.extend(comment.kids.iter().take(breadth).copied());
+                comment.depth = Some(depth);
+                comments.push(comment);
+            }
+        }
+        level = next;
+    }
+
+    // Replies left over once the depth or the total runs out.
+    truncated |= !level.is_empty();
+    CommentWalk {
+        comments,
+        truncated,
+    }
+}
+
+/// The maximum number of parents followed from a comment to its story.
+pub const MAX_ANCESTOR_DEPTH: usize = 100;
+
+/// Load the comments above `comment`, nearest first, and the item at the
+/// top of its thread.
+///
+/// The root is `None` if it fails to load or lies more than
+/// `MAX_ANCESTOR_DEPTH` parents up.
+pub(crate) async fn load_ancestors(
+    ctx: &Context<'_>,
+    comment: &Comment,
+) -> (Vec<Comment>, Option<Item>) {
+    let mut ancestors = Vec::new();
+    let mut parent = comment.parent;
+
+    for _ in 0..MAX_ANCESTOR_DEPTH {
+        match load_items(ctx, &[parent]).await.pop() {
+            Some(Item::Comment(comment)) => {
+                parent = comment.parent;
+                ancestors.push(comment);
+            }
+            root => return (ancestors, root),
+        }
+    }
+
+    (ancestors, None)
 }
 
 /// A story.
@@ -32,55 +440,513 @@ pub enum Item {
 #[graphql(complex)]
 pub struct Story {
     /// The item's unique id.
-    pub id: u32,
-    /// The total comment count.
-    pub descendants: u32,
-    /// The username of the item's author.
-    pub by: String,
-    /// The ids of the item's comments, in ranked display order.
-    pub kids: Option<Vec<u32>>,
-    /// The story's score.
+    pub id: ItemId,
+    /// The total comment count, if upstream reports one.
+    pub descendants: Option<u32>,
+    /// The username of the item's author. Missing on deleted stories.
+    pub by: Option<Username>,
+    /// The ids of the item's comments, in ranked display order. Empty if
+    /// the story has no comments.
+    #[serde(default)]
+    pub kids: Vec<ItemId>,
+    /// The story's score. Zero for deleted stories.
+    #[serde(default)]
     pub score: u32,
     #[graphql(skip)]
-    /// The title of the story.
-    pub title: String,
-    /// The URL of the story.
+    /// The title of the story. Missing on deleted stories.
+    pub title: Option<String>,
+    /// The URL of the story. Missing for text posts such as Ask HN.
     pub url: Option<String>,
-    /// The story text. HTML.
+    /// The story text. HTML. Only present for text posts.
     pub text: Option<String>,
     /// Creation date of the item, in Unix Time.
     pub time: u64,
+    /// Whether the item was deleted by its author.
+    #[serde(default)]
+    pub deleted: bool,
+    /// Whether the item was killed by moderators or flags.
+    #[serde(default)]
+    pub dead: bool,
+    /// When the item was fetched, in Unix Time. Set by the client.
+    #[serde(skip)]
+    #[graphql(skip)]
+    pub fetched_at: u64,
 }
 
 #[ComplexObject]
 impl Story {
+    /// The item's global id, for the Relay `Node` interface.
+    pub(crate) async fn node_id(&self) -> ID {
+        node::global_id("Story", self.id)
+    }
+
+    /// Where and when the item was fetched.
+    async fn meta(&self) -> ItemMeta {
+        ItemMeta::new(self.fetched_at)
+    }
+
     async fn title(&self) -> Option<&str> {
-        Some(&self.title)
+        self.title.as_deref()
     }
 
-    async fn author(&self) -> Option<&str> {
-        Some(&self.by)
+    /// The kind of the item: always `STORY`.
+    async fn item_type(&self) -> ItemType {
+        ItemType::Story
     }
 
-    async fn kids_connection(&self, ctx: &Context<'_>, limit: Option<usize>) -> Result<Vec<Item>> {
-        let limit = limit.unwrap_or_default();
-        let kids = self
-            .kids
-            .clone()
-            .unwrap_or_default()
+    /// Creation date of the item.
+    async fn created_at(&self) -> DateTime {
+        DateTime(self.time)
+    }
+
+    /// The time since the item was posted, as of the request. `format`
+    /// defaults to `RELATIVE`.
+    async fn age(&self, format: Option<AgeFormat>) -> String {
+        format_age(self.time, format)
+    }
+
+    async fn author(&self) -> Option<&Username> {
+        self.by.as_ref()
+    }
+
+    /// The profile of the item's author. `null` if it fails to load.
+    async fn author_user(&self, ctx: &Context<'_>) -> Option<User> {
+        load_user(ctx, self.by.as_ref()?).await
+    }
+
+    /// The address of the story's discussion page on Hacker News.
+    async fn discussion_url(&self) -> String {
+        format!("https://news.ycombinator.com/item?id={}", self.id)
+    }
+
+    /// The story's URL normalized for deduplication, without tracking
+    /// parameters.
+    async fn canonical_url(&self) -> Option<String> {
+        self.url.as_deref().and_then(url_utils::canonicalize)
+    }
+
+    /// The registrable domain of the story's URL, as shown next to titles
+    /// on HN, for example `github.com`. `null` for text posts.
+    async fn domain(&self) -> Option<String> {
+        self.url.as_deref().and_then(url_utils::domain)
+    }
+
+    /// The story text converted to plain text. For Ask HN stories this is
+    /// the question.
+    async fn plain_text(&self) -> Option<String> {
+        self.text.as_deref().map(|text| PlainText.process(text))
+    }
+
+    /// The story text converted to Markdown.
+    async fn markdown(&self) -> Option<String> {
+        self.text.as_deref().map(|text| Markdown.process(text))
+    }
+
+    /// The story's top-level comments in ranked order, with their reply
+    /// counts and author karma. Intended for Ask HN stories.
+    async fn top_answers(
+        &self,
+        ctx: &Context<'_>,
+        limit: Option<usize>,
+        offset: Option<usize>,
+    ) -> Result<Vec<Answer>> {
+        let kids = connection::slice(&self.kids, offset.unwrap_or(0), limit.unwrap_or(10));
+
+        let comments = load_items(ctx, kids)
+            .await
             .into_iter()
-            .take(limit)
+            .filter_map(|item| match item {
+                Item::Comment(comment) => Some(Comment {
+                    depth: Some(0),
+                    ..comment
+                }),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let authors = comments
+            .iter()
+            .filter_map(|c| c.by.clone())
             .collect::<Vec<_>>();
+        let karma = load_users(ctx, &authors)
+            .await
+            .into_iter()
+            .map(|user| (user.id, user.karma))
+            .collect::<HashMap<_, _>>();
+
+        Ok(comments
+            .into_iter()
+            .map(|comment| Answer {
+                replies: comment.kids.len(),
+                author_karma: comment.by.as_ref().and_then(|by| karma.get(by)).copied(),
+                comment,
+            })
+            .collect())
+    }
+
+    /// The number of comments posted in each `bucket` (default `1h`)
+    /// since the story was submitted, oldest first.
+    ///
+    /// The bucket is widened if needed to keep at most 1000 buckets, and
+    /// must be at least a second long. Only the first 2000 comments of the
+    /// thread are counted.
+    async fn activity_timeline(
+        &self,
+        ctx: &Context<'_>,
+        bucket: Option<Span>,
+    ) -> Result<Vec<ActivityBucket>> {
+        let bucket = bucket.map_or(DEFAULT_ACTIVITY_BUCKET, |bucket| bucket.0.as_secs());
+        if bucket == 0 {
+            return Err(Error::EmptyBucket);
+        }
 
-        Ok(ctx
-            .data_unchecked::<DataLoader<ItemLoader>>()
-            .load_many(kids)
+        let times = walk_comments(ctx, &self.kids, usize::MAX, usize::MAX)
             .await
-            .unwrap()
+            .comments
+            .into_iter()
+            .map(|comment| comment.time.saturating_sub(self.time))
+            .collect::<Vec<_>>();
+
+        let span = times.iter().copied().max().unwrap_or(0);
+        let bucket = bucket.max(span / MAX_BUCKETS + 1);
+        let mut counts = vec![0; (span / bucket) as usize + 1];
+        for offset in times {
+            counts[(offset / bucket) as usize] += 1;
+        }
+
+        Ok(counts
             .into_iter()
-            .map(|(_, v)| v)
+            .zip(0..)
+            .map(|(comments, n)| ActivityBucket {
+                start: self.time + n * bucket,
+                comments,
+            })
             .collect())
     }
+
+    /// The story's comments as a nested tree, in ranked order.
+    ///
+    /// Only `depth` levels are loaded, keeping the first `perLevelLimit`
+    /// replies to each comment; both default to unlimited. At most 2000
+    /// comments are loaded in total. Below the loaded part of the tree
+    /// `replies` is empty, while `comment.kids` still lists the replies.
+    ///
+    /// Deleted and dead comments are left out together with their replies,
+    /// unless `includeDead` is true.
+    async fn comment_tree(
+        &self,
+        ctx: &Context<'_>,
+        depth: Option<usize>,
+        per_level_limit: Option<usize>,
+        include_dead: Option<bool>,
+    ) -> Vec<CommentThread> {
+        let mut loaded = walk_comments(
+            ctx,
+            &self.kids,
+            depth.unwrap_or(usize::MAX),
+            per_level_limit.unwrap_or(usize::MAX),
+        )
+        .await
+        .comments
+        .into_iter()
+        .map(|comment| (comment.id, comment))
+        .collect();
+        CommentThread::build(&self.kids, &mut loaded, include_dead.unwrap_or(false))
+    }
+
+    /// A page of the story's top-level comments in ranked order.
+    ///
+    /// Omitting `first` returns the server's configured default, which is
+    /// all remaining comments up to the maximum page size unless set
+    /// otherwise.
+    ///
+    /// Deleted and dead comments are skipped unless `includeDead` is true;
+    /// a page then examines at most 1000 comments, as in
+    /// `User.submittedConnection`.
+    async fn kids_connection(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<usize>,
+        after: Option<String>,
+        offset: Option<usize>,
+        include_dead: Option<bool>,
+    ) -> Result<ItemConnection> {
+        let first = Some(first.unwrap_or_else(|| PageLimits::get(ctx).kids_page_size()));
+        let offset = offset.unwrap_or(0);
+        let mut page = if include_dead.unwrap_or(false) {
+            ItemConnection::load(ctx, &self.kids, after, offset, first).await?
+        } else {
+            ItemConnection::load_filtered(ctx, &self.kids, after, offset, first, Item::is_live)
+                .await?
+        };
+        for edge in &mut page.edges {
+            if let Item::Comment(comment) = &mut edge.node {
+                comment.depth = Some(0);
+            }
+        }
+        Ok(page)
+    }
+}
+
+/// A page of a list of items.
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct ItemConnection {
+    /// The items on this page, with their cursors.
+    pub edges: Vec<ItemEdge>,
+    /// Information to aid in pagination.
+    pub page_info: PageInfo,
+    /// The total number of items in the list.
+    pub total_count: usize,
+}
+
+#[ComplexObject]
+impl ItemConnection {
+    /// The items on this page, without their cursors.
+    async fn nodes(&self) -> Vec<&Item> {
+        self.edges.iter().map(|edge| &edge.node).collect()
+    }
+}
+
+impl ItemConnection {
+    /// Load the page of `ids` selected by the pagination arguments.
+    async fn load(
+        ctx: &Context<'_>,
+        ids: &[ItemId],
+        after: Option<String>,
+        offset: usize,
+        first: Option<usize>,
+    ) -> Result<Self> {
+        let range = connection::window(
+            ids.len(),
+            after.as_deref(),
+            offset,
+            first,
+            PageLimits::get(ctx).max,
+        )?;
+        let (edges, page_info) = connection::load_edges(ctx, ids, range).await;
+
+        Ok(Self {
+            edges,
+            page_info,
+            total_count: ids.len(),
+        })
+    }
+
+    /// Load the page of `ids` selected by the pagination arguments,
+    /// keeping only the items `keep` accepts.
+    async fn load_filtered(
+        ctx: &Context<'_>,
+        ids: &[ItemId],
+        after: Option<String>,
+        offset: usize,
+        first: Option<usize>,
+        keep: impl Fn(&Item) -> bool,
+    ) -> Result<Self> {
+        let max = PageLimits::get(ctx).max;
+        let start = connection::window(ids.len(), after.as_deref(), offset, Some(0), max)?.start;
+        let first = first.unwrap_or(connection::DEFAULT_PAGE_SIZE).min(max);
+        let (edges, page_info) =
+            connection::load_filtered_edges(ctx, ids, start, first, keep).await;
+
+        Ok(Self {
+            edges,
+            page_info,
+            total_count: ids.len(),
+        })
+    }
+}
+
+/// One of the story lists published by HN.
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum StoryList {
+    /// Top stories.
+    Top,
+    /// New stories.
+    New,
+    /// Best stories.
+    Best,
+    /// Ask HN stories.
+    Ask,
+    /// Show HN stories.
+    Show,
+    /// Job stories.
+    Job,
+}
+
+/// Where a list or item was read from.
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Source {
+    /// Fetched from the HN API while serving the request.
+    Upstream,
+}
+
+/// Provenance of an item.
+#[derive(SimpleObject)]
+pub struct ItemMeta {
+    /// Where the item was read from.
+    pub source: Source,
+    /// When the item was fetched, in Unix Time.
+    pub fetched_at: u64,
+    /// The age of the fetched item when the field was resolved, in seconds.
+    pub cache_age_seconds: u64,
+}
+
+impl ItemMeta {
+    fn new(fetched_at: u64) -> Self {
+        Self {
+            source: Source::Upstream,
+            fetched_at,
+            cache_age_seconds: now().saturating_sub(fetched_at),
+        }
+    }
+}
+
+/// A page of a story list, together with information about where the
+/// list came from.
+#[derive(SimpleObject)]
+#[graphql(complex)]
+pub struct StoryConnection {
+    /// The items on this page, with their cursors.
+    pub edges: Vec<ItemEdge>,
+    /// Information to aid in pagination.
+    pub page_info: PageInfo,
+    /// The total number of items in the list.
+    pub total_count: usize,
+    /// When the list's ids were fetched, in Unix Time.
+    pub fetched_at: u64,
+    /// Where the list's ids were read from.
+    pub source: Source,
+    /// Which list this is.
+    pub list_kind: StoryList,
+}
+
+#[ComplexObject]
+impl StoryConnection {
+    /// The items on this page, without their cursors.
+    async fn nodes(&self) -> Vec<&Item> {
+        self.edges.iter().map(|edge| &edge.node).collect()
+    }
+}
+
+impl StoryConnection {
+    /// Fetch the page of `list` selected by the pagination arguments,
+    /// skipping deleted and dead items unless `include_dead` is set.
+    pub(crate) async fn load(
+        ctx: &Context<'_>,
+        list: StoryList,
+        after: Option<String>,
+        offset: usize,
+        first: Option<usize>,
+        include_dead: bool,
+    ) -> Result<Self> {
+        let client = ctx.data_unchecked::<HnClient>();
+        let fetched_at = now();
+        let ids = client.get_stories(list).await?;
+        let max = PageLimits::get(ctx).max;
+        let (edges, page_info) = if include_dead {
+            let range = connection::window(ids.len(), after.as_deref(), offset, first, max)?;
+            connection::load_edges(ctx, &ids, range).await
+        } else {
+            let start =
+                connection::window(ids.len(), after.as_deref(), offset, Some(0), max)?.start;
+            let first = first.unwrap_or(connection::DEFAULT_PAGE_SIZE).min(max);
+            connection::load_filtered_edges(ctx, &ids, start, first, Item::is_live).await
+        };
+
+        Ok(Self {
+            edges,
+            page_info,
+            total_count: ids.len(),
+            fetched_at,
+            source: Source::Upstream,
+            list_kind: list,
+        })
+    }
+}
+
+/// The current time, in Unix Time.
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// A representation of the HTML text of items.
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TextFormat {
+    /// Plain text, with paragraphs separated by blank lines.
+    PlainText,
+    /// Markdown.
+    Markdown,
+    /// HTML reduced to the tags HN emits, safe to embed in a page.
+    Html,
+}
+
+/// How `age` fields report the time since an item was posted.
+#[derive(Enum, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AgeFormat {
+    /// The number of seconds, for example `10800`.
+    Seconds,
+    /// A phrase in the style of the HN front page, for example
+    /// `3 hours ago`.
+    Relative,
+}
+
+impl AgeFormat {
+    /// Format an age of `seconds`.
+    pub fn format(self, seconds: u64) -> String {
+        const UNITS: &[(u64, &str)] = &[
+            (365 * 24 * 3600, "year"),
+            (30 * 24 * 3600, "month"),
+            (24 * 3600, "day"),
+            (3600, "hour"),
+            (60, "minute"),
+        ];
+        if self == AgeFormat::Seconds {
+            return seconds.to_string();
+        }
+        match UNITS.iter().find(|&&(length, _)| seconds >= length) {
+            Some(&(length, unit)) => {
+                let count = seconds / length;
+                let plural = if count == 1 { "" } else { "s" };
+                format!("{} {}{} ago", count, unit, plural)
+            }
+            None => "just now".to_string(),
+        }
+    }
+}
+
+/// Format the age of an item created at `time`, in Unix Time.
+fn format_age(time: u64, format: Option<AgeFormat>) -> String {
+    format
+        .unwrap_or(AgeFormat::Relative)
+        .format(now().saturating_sub(time))
+}
+
+/// The maximum number of buckets returned by `activityTimeline`.
+const MAX_BUCKETS: u64 = 1000;
+
+/// The default bucket of `activityTimeline`: one hour, in seconds.
+const DEFAULT_ACTIVITY_BUCKET: u64 = 60 * 60;
+
+/// The number of comments posted within one time bucket.
+#[derive(Debug, Clone, SimpleObject)]
+pub struct ActivityBucket {
+    /// The start of the bucket, in Unix Time.
+    pub start: u64,
+    /// The number of comments posted within the bucket.
+    pub comments: usize,
+}
+
+/// A top-level answer to an Ask HN question.
+#[derive(Debug, Clone, SimpleObject)]
+pub struct Answer {
+    /// The answering comment.
+    pub comment: Comment,
+    /// The number of direct replies to the answer.
+    pub replies: usize,
+    /// The karma of the answer's author, if their profile could be loaded.
+    pub author_karma: Option<u32>,
 }
 
 /// A comment.
@@ -88,26 +954,298 @@ impl Story {
 #[graphql(complex)]
 pub struct Comment {
     /// The item's unique id.
-    pub id: u32,
-    /// The username of the item's author.
-    pub by: String,
-    /// The ids of the item's comments, in ranked display order.
-    pub kids: Option<Vec<u32>>,
-    /// The comment's parent: either another comment or the relevant story.
-    pub parent: u32,
-    /// The comment text. HTML.
+    pub id: ItemId,
+    /// The username of the item's author. Missing on deleted comments.
+    pub by: Option<Username>,
+    /// The ids of the item's replies, in ranked display order. Empty if
+    /// the comment has no replies.
+    #[serde(default)]
+    pub kids: Vec<ItemId>,
+    /// The id of the comment's parent: either another comment or the
+    /// relevant story.
+    #[graphql(skip)]
+    pub parent: ItemId,
+    /// The comment text. HTML. Empty for deleted comments.
+    #[serde(default)]
     pub text: String,
     /// Creation date of the item, in Unix Time.
     pub time: u64,
+    /// The comment's nesting depth below its story, 0 for top-level
+    /// comments. Only known when the comment was reached by walking a
+    /// thread; `null` otherwise.
+    #[serde(skip)]
+    pub depth: Option<usize>,
+    /// Whether the item was deleted by its author.
+    #[serde(default)]
+    pub deleted: bool,
+    /// Whether the item was killed by moderators or flags.
+    #[serde(default)]
+    pub dead: bool,
+    /// When the item was fetched, in Unix Time. Set by the client.
+    #[serde(skip)]
+    #[graphql(skip)]
+    pub fetched_at: u64,
 }
 
 #[ComplexObject]
 impl Comment {
+    /// The item's global id, for the Relay `Node` interface.
+    pub(crate) async fn node_id(&self) -> ID {
+        node::global_id("Comment", self.id)
+    }
+
+    /// Where and when the item was fetched.
+    async fn meta(&self) -> ItemMeta {
+        ItemMeta::new(self.fetched_at)
+    }
+
     async fn title(&self) -> Option<&str> {
         None
     }
-    async fn author(&self) -> Option<&str> {
-        Some(&self.by)
+
+    /// The kind of the item: always `COMMENT`.
+    async fn item_type(&self) -> ItemType {
+        ItemType::Comment
+    }
+
+    /// Creation date of the item.
+    async fn created_at(&self) -> DateTime {
+        DateTime(self.time)
+    }
+
+    /// The time since the item was posted, as of the request. `format`
+    /// defaults to `RELATIVE`.
+    async fn age(&self, format: Option<AgeFormat>) -> String {
+        format_age(self.time, format)
+    }
+
+    async fn author(&self) -> Option<&Username> {
+        self.by.as_ref()
+    }
+
+    /// Always `null`: comment scores are not public.
+    async fn score(&self) -> Option<&u32> {
+        None
+    }
+
+    /// Always `null`: comments have no URL.
+    async fn url(&self) -> &Option<String> {
+        &NO_URL
+    }
+
+    /// The profile of the item's author. `null` if it fails to load.
+    async fn author_user(&self, ctx: &Context<'_>) -> Option<User> {
+        load_user(ctx, self.by.as_ref()?).await
+    }
+
+    /// The id of the comment's parent: either another comment or the
+    /// relevant story.
+    async fn parent_id(&self) -> ItemId {
+        self.parent
+    }
+
+    /// The comment's parent: either another comment or the relevant story.
+    /// `null` if it fails to load.
+    async fn parent(&self, ctx: &Context<'_>) -> Option<Item> {
+        load_items(ctx, &[self.parent]).await.pop()
+    }
+
+    /// The story the comment belongs to, found by following `parent` up
+    /// at most 100 levels. `null` if the thread hangs off
+    /// something other than a story, such as a poll, or the story can't be
+    /// reached.
+    async fn root_story(&self, ctx: &Context<'_>) -> Option<Story> {
+        match load_ancestors(ctx, self).await.1 {
+            Some(Item::Story(story)) => Some(story),
+            _ => None,
+        }
+    }
+
+    /// Whether readers should show the comment collapsed at first: it is
+    /// dead, its parent comment is dead or deleted, it is nested very
+    /// deeply, or it is a short reply without replies. The thresholds are
+    /// set by the server.
+    async fn collapsed_by_default(&self, ctx: &Context<'_>) -> bool {
+        CollapseRules::get(ctx).collapses(ctx, self).await
+    }
+
+    /// The comment text converted to plain text.
+    async fn plain_text(&self) -> String {
+        PlainText.process(&self.text)
+    }
+
+    /// The comment text converted to Markdown.
+    async fn markdown(&self) -> String {
+        Markdown.process(&self.text)
+    }
+
+    /// The paragraphs the comment quotes, by HN's `>` convention.
+    async fn quotes(&self) -> Vec<String> {
+        Quotes.process(&self.text)
+    }
+}
+
+/// A comment with enough of its thread to render a permalink page.
+#[derive(SimpleObject)]
+pub struct CommentContext {
+    /// The requested comment.
+    pub comment: Comment,
+    /// The comments above the requested one, outermost first.
+    pub ancestors: Vec<Comment>,
+    /// The item at the top of the thread, usually a story. `null` if it
+    /// could not be loaded.
+    pub root: Option<Item>,
+    /// The replies to the requested comment, in ranked order. Deleted and
+    /// dead replies are left out unless `includeDead` is true.
+    pub replies: Vec<CommentThread>,
+}
+
+impl CommentContext {
+    /// Load the context of `comment`, keeping `ancestors` of its parent
+    /// comments and `replies` levels of replies.
+    pub(crate) async fn load(
+        ctx: &Context<'_>,
+        mut comment: Comment,
+        ancestors: usize,
+        replies: usize,
+        include_dead: bool,
+    ) -> Self {
+        let (mut parents, root) = load_ancestors(ctx, &comment).await;
+        // Depths are only known if the walk reached the top of the thread.
+        let depth = root.as_ref().map(|_| parents.len());
+        comment.depth = depth;
+        for (n, parent) in parents.iter_mut().enumerate() {
+            parent.depth = depth.map(|depth| depth - n - 1);
+        }
+        parents.truncate(ancestors);
+        parents.reverse();
+
+        let mut loaded = walk_comments(ctx, &comment.kids, replies, usize::MAX)
+            .await
+            .comments
+            .into_iter()
+            .map(|mut reply| {
+                reply.depth = depth
+                    .zip(reply.depth)
+                    .map(|(depth, below)| depth + 1 + below);
+                (reply.id, reply)
+            })
+            .collect();
+        let replies = CommentThread::build(&comment.kids, &mut loaded, include_dead);
+
+        Self {
+            comment,
+            ancestors: parents,
+            root,
+            replies,
+        }
+    }
+}
+
+/// A comment and the loaded part of the tree of replies below it.
+///
+/// `replies` is empty below the requested depth; `comment.kids` still
+/// lists the replies that were not loaded.
+#[derive(SimpleObject)]
+pub struct CommentThread {
+    /// The comment.
+    pub comment: Comment,
+    /// The loaded replies to the comment, in ranked order.
+    pub replies: Vec<CommentThread>,
+}
+
+impl CommentThread {
+    /// Assemble the threads starting at `ids` from `loaded` comments,
+    /// leaving out deleted and dead comments and their replies unless
+    /// `include_dead` is set.
+    fn build(
+        ids: &[ItemId],
+        loaded: &mut HashMap<ItemId, Comment>,
+        include_dead: bool,
+    ) -> Vec<Self> {
+        let mut threads = Vec::new();
+        for id in ids {
+            if let Some(comment) = loaded.remove(id) {
+                if !include_dead && (comment.deleted || comment.dead) {
+                    continue;
+                }
+                let replies = Self::build(&comment.kids, loaded, include_dead);
+                threads.push(Self { comment, replies });
+            }
+        }
+        threads
+    }
+}
+
+/// All submissions of a URL, with the one holding the discussion singled
+/// out.
+#[derive(SimpleObject)]
+pub struct CanonicalStory {
+    /// The submission with the highest score.
+    pub story: Story,
+    /// The other submissions of the same URL, highest score first.
+    pub other_submissions: Vec<Story>,
+}
+
+/// A story and its comment tree, loaded together so that the counts
+/// describe the same snapshot.
+#[derive(SimpleObject)]
+pub struct StoryThread {
+    /// The story, fetched once for the whole request.
+    pub story: Story,
+    /// The loaded comment tree, in ranked order.
+    pub comments: Vec<CommentThread>,
+    /// The number of live comments in the loaded tree. Deleted and dead
+    /// comments, and comments that fail to load, are not counted, even
+    /// when `includeDead` returns them.
+    pub loaded_count: usize,
+    /// Whether the whole tree was loaded, rather than cut off by the depth,
+    /// per-level or total limits.
+    pub complete: bool,
+    /// Whether the tree is complete and `loadedCount` agrees with the
+    /// story's `descendants`. When it doesn't, the story changed while the
+    /// tree was being fetched, and `loadedCount` is the better count.
+    pub consistent: bool,
+}
+
+impl StoryThread {
+    /// Load the tree below `story`, at most `depth` levels deep and
+    /// keeping the first `breadth` replies to each comment.
+    pub(crate) async fn load(
+        ctx: &Context<'_>,
+        story: Story,
+        depth: Option<usize>,
+        breadth: Option<usize>,
+        include_dead: bool,
+    ) -> Self {
+        let CommentWalk {
+            comments,
+            truncated,
+        } = walk_comments(
+            ctx,
+            &story.kids,
+            depth.unwrap_or(usize::MAX),
+            breadth.unwrap_or(usize::MAX),
+        )
+        .await;
+        let complete = !truncated;
+        let loaded_count = comments
+            .iter()
+            .filter(|comment| !comment.deleted && !comment.dead)
+            .count();
+        let consistent = complete && story.descendants == Some(loaded_count as u32);
+
+        let mut loaded = comments
+            .into_iter()
+            .map(|comment| (comment.id, comment))
+            .collect();
+        Self {
+            comments: CommentThread::build(&story.kids, &mut loaded, include_dead),
+            story,
+            loaded_count,
+            complete,
+            consistent,
+        }
     }
 }
 
@@ -116,27 +1254,82 @@ impl Comment {
 #[graphql(complex)]
 pub struct Job {
     /// The item's unique id.
-    pub id: u32,
-    /// The story's score, or the votes for a pollopt.
+    pub id: ItemId,
+    /// The username of the posting's author, if upstream reports one.
+    pub by: Option<Username>,
+    /// The job's score. Zero for deleted jobs.
+    #[serde(default)]
     pub score: u32,
-    /// The job text. HTML.
+    /// The job text. HTML. Only present for postings hosted on HN.
     pub text: Option<String>,
     /// Creation date of the item, in Unix Time.
     pub time: u64,
     #[graphql(skip)]
-    /// The title of the job.
-    pub title: String,
-    /// The URL of the story.
+    /// The title of the job. Missing on deleted jobs.
+    pub title: Option<String>,
+    /// The URL of the job posting. Missing or empty for postings hosted
+    /// on HN.
     pub url: Option<String>,
+    /// Whether the item was deleted by its author.
+    #[serde(default)]
+    pub deleted: bool,
+    /// Whether the item was killed by moderators or flags.
+    #[serde(default)]
+    pub dead: bool,
+    /// When the item was fetched, in Unix Time. Set by the client.
+    #[serde(skip)]
+    #[graphql(skip)]
+    pub fetched_at: u64,
 }
 
 #[ComplexObject]
 impl Job {
+    /// The item's global id, for the Relay `Node` interface.
+    pub(crate) async fn node_id(&self) -> ID {
+        node::global_id("Job", self.id)
+    }
+
+    /// Where and when the item was fetched.
+    async fn meta(&self) -> ItemMeta {
+        ItemMeta::new(self.fetched_at)
+    }
+
     async fn title(&self) -> Option<&str> {
-        Some(&self.title)
+        self.title.as_deref()
     }
-    async fn author(&self) -> Option<&str> {
-        None
+
+    /// The kind of the item: always `JOB`.
+    async fn item_type(&self) -> ItemType {
+        ItemType::Job
+    }
+
+    /// Creation date of the item.
+    async fn created_at(&self) -> DateTime {
+        DateTime(self.time)
+    }
+
+    /// The time since the item was posted, as of the request. `format`
+    /// defaults to `RELATIVE`.
+    async fn age(&self, format: Option<AgeFormat>) -> String {
+        format_age(self.time, format)
+    }
+
+    async fn author(&self) -> Option<&Username> {
+        self.by.as_ref()
+    }
+
+    /// Always empty: jobs can't be replied to.
+    async fn kids(&self) -> &Vec<ItemId> {
+        &NO_KIDS
+    }
+
+    /// Company, location and compensation hints parsed from the posting.
+    #[cfg(feature = "job-parsing")]
+    async fn parsed(&self) -> crate::job_parser::ParsedJob {
+        crate::job_parser::parse(
+            self.title.as_deref().unwrap_or_default(),
+            self.text.as_deref(),
+        )
     }
 }
 
@@ -145,34 +1338,170 @@ impl Job {
 #[graphql(complex)]
 pub struct Poll {
     /// The item's unique id.
-    pub id: u32,
-    /// The username of the item's author.
-    pub by: String,
-    /// The total comment count.
-    pub descendants: u32,
-    /// The ids of the item's comments, in ranked display order.
-    pub kids: Option<Vec<u32>>,
+    pub id: ItemId,
+    /// The username of the item's author. Missing on deleted polls.
+    pub by: Option<Username>,
+    /// The total comment count, if upstream reports one.
+    pub descendants: Option<u32>,
+    /// The ids of the item's comments, in ranked display order. Empty if
+    /// the poll has no comments.
+    #[serde(default)]
+    pub kids: Vec<ItemId>,
     /// A list of related pollopts, in display order.
-    pub parts: Option<Vec<u32>>,
-    /// The story's score.
+    #[serde(default)]
+    pub parts: Vec<ItemId>,
+    /// The poll's score. Zero for deleted polls.
+    #[serde(default)]
     pub score: u32,
     #[graphql(skip)]
-    /// The title of the story.
-    pub title: String,
-    /// The story text. HTML.
+    /// The title of the poll. Missing on deleted polls.
+    pub title: Option<String>,
+    /// The poll text. HTML. Often empty.
     pub text: Option<String>,
     /// Creation date of the item, in Unix Time.
     pub time: u64,
+    /// Whether the item was deleted by its author.
+    #[serde(default)]
+    pub deleted: bool,
+    /// Whether the item was killed by moderators or flags.
+    #[serde(default)]
+    pub dead: bool,
+    /// When the item was fetched, in Unix Time. Set by the client.
+    #[serde(skip)]
+    #[graphql(skip)]
+    pub fetched_at: u64,
 }
 
 #[ComplexObject]
 impl Poll {
+    /// The item's global id, for the Relay `Node` interface.
+    pub(crate) async fn node_id(&self) -> ID {
+        node::global_id("Poll", self.id)
+    }
+
+    /// Where and when the item was fetched.
+    async fn meta(&self) -> ItemMeta {
+        ItemMeta::new(self.fetched_at)
+    }
+
     async fn title(&self) -> Option<&str> {
-        Some(&self.title)
+        self.title.as_deref()
+    }
+
+    /// The kind of the item: always `POLL`.
+    async fn item_type(&self) -> ItemType {
+        ItemType::Poll
+    }
+
+    /// Creation date of the item.
+    async fn created_at(&self) -> DateTime {
+        DateTime(self.time)
+    }
+
+    /// The time since the item was posted, as of the request. `format`
+    /// defaults to `RELATIVE`.
+    async fn age(&self, format: Option<AgeFormat>) -> String {
+        format_age(self.time, format)
     }
-    async fn author(&self) -> Option<&str> {
-        Some(&self.by)
+
+    async fn author(&self) -> Option<&Username> {
+        self.by.as_ref()
+    }
+
+    /// Always `null`: polls have no URL.
+    async fn url(&self) -> &Option<String> {
+        &NO_URL
+    }
+
+    /// The profile of the item's author. `null` if it fails to load.
+    async fn author_user(&self, ctx: &Context<'_>) -> Option<User> {
+        load_user(ctx, self.by.as_ref()?).await
+    }
+
+    /// A page of the poll's options in display order.
+    ///
+    /// Omitting `first` returns all remaining options, up to the server's
+    /// maximum page size.
+    async fn parts_connection(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<usize>,
+        after: Option<String>,
+        offset: Option<usize>,
+    ) -> Result<ItemConnection> {
+        let first = first.unwrap_or_else(|| PageLimits::get(ctx).max);
+        ItemConnection::load(ctx, &self.parts, after, offset.unwrap_or(0), Some(first)).await
     }
+
+    /// The poll's options ranked by votes, with their share of the total.
+    async fn results(&self, ctx: &Context<'_>) -> Result<PollResults> {
+        let mut options = self.load_options(ctx).await?;
+        let total_votes = options.iter().map(|opt| opt.score).sum();
+        options.sort_by_key(|opt| Reverse(opt.score));
+
+        let options = options
+            .into_iter()
+            .map(|option| PollResult {
+                percentage: if total_votes == 0 {
+                    0.0
+                } else {
+                    f64::from(option.score) * 100.0 / f64::from(total_votes)
+                },
+                option,
+            })
+            .collect::<Vec<_>>();
+        let leading = options.first().filter(|res| res.option.score > 0).cloned();
+
+        Ok(PollResults {
+            options,
+            leading,
+            total_votes,
+        })
+    }
+
+    /// The total number of votes cast across all of the poll's options.
+    async fn total_votes(&self, ctx: &Context<'_>) -> Result<u32> {
+        Ok(self
+            .load_options(ctx)
+            .await?
+            .iter()
+            .map(|opt| opt.score)
+            .sum())
+    }
+}
+
+impl Poll {
+    /// Load the poll's options in display order.
+    async fn load_options(&self, ctx: &Context<'_>) -> Result<Vec<Pollopt>> {
+        Ok(load_items(ctx, &self.parts)
+            .await
+            .into_iter()
+            .filter_map(|item| match item {
+                Item::Pollopt(opt) => Some(opt),
+                _ => None,
+            })
+            .collect())
+    }
+}
+
+/// A poll option together with its share of the poll's votes.
+#[derive(Debug, Clone, SimpleObject)]
+pub struct PollResult {
+    /// The poll option.
+    pub option: Pollopt,
+    /// The option's share of all votes cast in the poll, as a percentage.
+    pub percentage: f64,
+}
+
+/// A snapshot of a poll's results.
+#[derive(Debug, Clone, SimpleObject)]
+pub struct PollResults {
+    /// The poll's options, sorted by descending score.
+    pub options: Vec<PollResult>,
+    /// The option with the most votes, or `null` if no votes have been cast.
+    pub leading: Option<PollResult>,
+    /// The total number of votes cast across all options.
+    pub total_votes: u32,
 }
 
 /// A poll option belonging to a poll.
@@ -180,52 +1509,344 @@ impl Poll {
 #[graphql(complex)]
 pub struct Pollopt {
     /// The item's unique id.
-    pub id: u32,
-    /// The username of the item's author.
-    pub by: String,
+    pub id: ItemId,
+    /// The username of the item's author. Missing on deleted poll options.
+    pub by: Option<Username>,
     /// The pollopt's associated poll.
-    pub poll: u32,
-    /// The votes for a pollopt.
+    pub poll: ItemId,
+    /// The votes for a pollopt. Zero for deleted poll options.
+    #[serde(default)]
     pub score: u32,
     /// The story text. HTML.
     pub text: Option<String>,
     /// Creation date of the item, in Unix Time.
     pub time: u64,
+    /// Whether the item was deleted by its author.
+    #[serde(default)]
+    pub deleted: bool,
+    /// Whether the item was killed by moderators or flags.
+    #[serde(default)]
+    pub dead: bool,
+    /// When the item was fetched, in Unix Time. Set by the client.
+    #[serde(skip)]
+    #[graphql(skip)]
+    pub fetched_at: u64,
 }
 
 #[ComplexObject]
 impl Pollopt {
+    /// The item's global id, for the Relay `Node` interface.
+    pub(crate) async fn node_id(&self) -> ID {
+        node::global_id("Pollopt", self.id)
+    }
+
+    /// Where and when the item was fetched.
+    async fn meta(&self) -> ItemMeta {
+        ItemMeta::new(self.fetched_at)
+    }
+
     async fn title(&self) -> Option<&str> {
         None
     }
-    async fn author(&self) -> Option<&str> {
-        Some(&self.by)
+
+    /// The kind of the item: always `POLLOPT`.
+    async fn item_type(&self) -> ItemType {
+        ItemType::Pollopt
+    }
+
+    /// Creation date of the item.
+    async fn created_at(&self) -> DateTime {
+        DateTime(self.time)
+    }
+
+    /// The time since the item was posted, as of the request. `format`
+    /// defaults to `RELATIVE`.
+    async fn age(&self, format: Option<AgeFormat>) -> String {
+        format_age(self.time, format)
+    }
+
+    async fn author(&self) -> Option<&Username> {
+        self.by.as_ref()
+    }
+
+    /// Always empty: poll options can't be replied to.
+    async fn kids(&self) -> &Vec<ItemId> {
+        &NO_KIDS
+    }
+
+    /// Always `null`: poll options have no URL.
+    async fn url(&self) -> &Option<String> {
+        &NO_URL
+    }
+
+    /// The profile of the item's author. `null` if it fails to load.
+    async fn author_user(&self, ctx: &Context<'_>) -> Option<User> {
+        load_user(ctx, self.by.as_ref()?).await
+    }
+
+    /// The poll the option belongs to. `null` if it fails to load.
+    async fn poll_item(&self, ctx: &Context<'_>) -> Option<Poll> {
+        match load_items(ctx, &[self.poll]).await.pop() {
+            Some(Item::Poll(poll)) => Some(poll),
+            _ => None,
+        }
+    }
+}
+
+/// An item that could not be decoded, because its type is unknown or it
+/// is malformed.
+///
+/// Only the fields that could be read are filled in; `raw` holds the
+/// item as returned by the API.
+#[derive(Debug, Clone, SimpleObject)]
+#[graphql(complex)]
+pub struct UnknownItem {
+    /// The item's unique id.
+    pub id: ItemId,
+    /// The item's `type` tag. Missing if it is not a string.
+    #[graphql(name = "type")]
+    pub type_tag: Option<String>,
+    /// Creation date of the item, in Unix Time. Zero if missing.
+    pub time: u64,
+    /// Whether the item was deleted by its author.
+    pub deleted: bool,
+    /// Whether the item was killed by moderators or flags.
+    pub dead: bool,
+    /// The item as returned by the API, as JSON text.
+    pub raw: String,
+    /// The raw item's `title`, if it is a string.
+    #[graphql(skip)]
+    pub title: Option<String>,
+    /// The raw item's `by`, if it is a string.
+    #[graphql(skip)]
+    pub by: Option<Username>,
+    /// When the item was fetched, in Unix Time. Set by the client.
+    #[graphql(skip)]
+    pub fetched_at: u64,
+}
+
+impl UnknownItem {
+    /// Read what can be read from `value`, or return `None` if it has no
+    /// id.
+    fn from_raw(value: serde_json::Value) -> Option<Self> {
+        let id = ItemId(value.get("id")?.as_u64()?);
+        let string = |name| value.get(name).and_then(|field| field.as_str());
+        let flag = |name| value.get(name).and_then(|field| field.as_bool());
+        Some(Self {
+            id,
+            type_tag: string("type").map(Into::into),
+            time: value
+                .get("time")
+                .and_then(|time| time.as_u64())
+                .unwrap_or(0),
+            deleted: flag("deleted").unwrap_or(false),
+            dead: flag("dead").unwrap_or(false),
+            title: string("title").map(Into::into),
+            by: string("by").map(|by| Username(by.to_string())),
+            raw: value.to_string(),
+            fetched_at: 0,
+        })
+    }
+}
+
+#[ComplexObject]
+impl UnknownItem {
+    /// The item's global id, for the Relay `Node` interface.
+    pub(crate) async fn node_id(&self) -> ID {
+        node::global_id("UnknownItem", self.id)
+    }
+
+    /// Where and when the item was fetched.
+    async fn meta(&self) -> ItemMeta {
+        ItemMeta::new(self.fetched_at)
+    }
+
+    /// The raw item's `title`, if it is a string.
+    async fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// The kind of the item: always `UNKNOWN`.
+    async fn item_type(&self) -> ItemType {
+        ItemType::Unknown
+    }
+
+    /// Creation date of the item.
+    async fn created_at(&self) -> DateTime {
+        DateTime(self.time)
+    }
+
+    /// The time since the item was posted, as of the request. `format`
+    /// defaults to `RELATIVE`.
+    async fn age(&self, format: Option<AgeFormat>) -> String {
+        format_age(self.time, format)
+    }
+
+    /// The raw item's `by`, if it is a string.
+    async fn author(&self) -> Option<&Username> {
+        self.by.as_ref()
+    }
+
+    /// Always `null`: the score can't be trusted.
+    async fn score(&self) -> Option<&u32> {
+        None
+    }
+
+    /// Always `null`: the URL can't be trusted.
+    async fn url(&self) -> &Option<String> {
+        &NO_URL
+    }
+
+    /// Always empty: the replies can't be trusted.
+    async fn kids(&self) -> &Vec<ItemId> {
+        &NO_KIDS
     }
 }
 
 /// A user profile.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, SimpleObject)]
+#[graphql(complex)]
 pub struct User {
     /// The user's unique username. Case-sensitive.
-    pub id: String,
+    pub id: Username,
     /// Creation date of the user, in Unix Time.
     pub created: u64,
     /// The user's karma.
     pub karma: u32,
     /// Delay in minutes between a comment's creation and its visibility to
-    /// other users.
+    /// other users. Missing if the user never set one.
     pub delay: Option<u32>,
     /// The user's optional self-description. HTML.
     pub about: Option<String>,
-    /// List of the user's stories, polls and comments.
-    pub submitted: Vec<u32>,
+    /// List of the user's stories, polls and comments. Empty if the user
+    /// has never submitted anything.
+    #[serde(default)]
+    pub submitted: Vec<ItemId>,
+}
+
+#[ComplexObject]
+impl User {
+    /// The user's global id, for the Relay `Node` interface.
+    pub(crate) async fn node_id(&self) -> ID {
+        node::global_id("User", &self.id)
+    }
+
+    /// Creation date of the user.
+    async fn created_at(&self) -> DateTime {
+        DateTime(self.created)
+    }
+
+    /// The user's self-description converted to plain text, or `null` if
+    /// it is missing or blank.
+    async fn about_text(&self) -> Option<String> {
+        self.about
+            .as_deref()
+            .map(|about| PlainText.process(about))
+            .filter(|text| !text.is_empty())
+    }
+
+    /// The targets of the links in the user's self-description.
+    async fn about_links(&self) -> Vec<String> {
+        self.about
+            .as_deref()
+            .map(|about| Links.process(about))
+            .unwrap_or_default()
+    }
+
+    /// A page of the user's submissions, newest first.
+    ///
+    /// With `type`, only submissions of that type are returned, and a page
+    /// examines at most 1000 submissions; it may then come back short, and
+    /// paging on from its `endCursor` continues the search. The same holds
+    /// for deleted and dead submissions, which are skipped unless
+    /// `includeDead` is true. `offset` and `totalCount` count every
+    /// submission.
+    async fn submitted_connection(
+        &self,
+        ctx: &Context<'_>,
+        first: Option<usize>,
+        after: Option<String>,
+        offset: Option<usize>,
+        #[graphql(name = "type")] item_type: Option<ItemType>,
+        include_dead: Option<bool>,
+    ) -> Result<ItemConnection> {
+        let offset = offset.unwrap_or(0);
+        let include_dead = include_dead.unwrap_or(false);
+        if item_type.is_none() && include_dead {
+            return ItemConnection::load(ctx, &self.submitted, after, offset, first).await;
+        }
+        ItemConnection::load_filtered(ctx, &self.submitted, after, offset, first, |item| {
+            item_type.is_none_or(|item_type| item.kind() == item_type)
+                && (include_dead || item.is_live())
+        })
+        .await
+    }
 }
 
 /// A list of recently updated items and users.
 #[derive(Debug, Clone, Deserialize)]
 pub struct Updates {
     /// A list of recently changed items.
-    pub items: Vec<u32>,
+    pub items: Vec<ItemId>,
     /// A list of recently changed usernames.
-    pub profiles: Vec<String>,
+    pub profiles: Vec<Username>,
+}
+
+/// A list of recently updated items and users.
+///
+/// Every field takes `offset` and `limit`. The hydrated `items` and
+/// `profiles` are capped at the server's maximum page size; `itemIds` and
+/// `usernames` are cheap and unbounded.
+#[Object]
+impl Updates {
+    /// The ids of the recently changed items.
+    async fn item_ids(&self, offset: Option<usize>, limit: Option<usize>) -> &[ItemId] {
+        connection::slice(
+            &self.items,
+            offset.unwrap_or(0),
+            limit.unwrap_or(usize::MAX),
+        )
+    }
+
+    /// The recently changed items.
+    async fn items(
+        &self,
+        ctx: &Context<'_>,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> Vec<Item> {
+        let max = PageLimits::get(ctx).max;
+        let limit = limit.unwrap_or(max).min(max);
+        load_items(
+            ctx,
+            connection::slice(&self.items, offset.unwrap_or(0), limit),
+        )
+        .await
+    }
+
+    /// The usernames of the recently changed profiles.
+    async fn usernames(&self, offset: Option<usize>, limit: Option<usize>) -> &[Username] {
+        connection::slice(
+            &self.profiles,
+            offset.unwrap_or(0),
+            limit.unwrap_or(usize::MAX),
+        )
+    }
+
+    /// The recently changed profiles. Profiles that fail to load are
+    /// skipped.
+    async fn profiles(
+        &self,
+        ctx: &Context<'_>,
+        offset: Option<usize>,
+        limit: Option<usize>,
+    ) -> Vec<User> {
+        let max = PageLimits::get(ctx).max;
+        let limit = limit.unwrap_or(max).min(max);
+        load_users(
+            ctx,
+            connection::slice(&self.profiles, offset.unwrap_or(0), limit),
+        )
+        .await
+    }
 }
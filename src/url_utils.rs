@@ -0,0 +1,152 @@
+//! Helpers for working with the URLs that stories link to.
+
+use url::{Host, Url};
+
+/// Query parameters that only exist to track where a click came from.
+fn is_tracking_param(name: &str) -> bool {
+    name.starts_with("utm_") || name == "fbclid" || name == "gclid"
+}
+
+/// Second-level labels under which country-code domains register names,
+/// as in `bbc.co.uk`.
+static COUNTRY_SECOND_LEVELS: &[&str] = &["ac", "co", "com", "edu", "gov", "net", "org"];
+
+/// Normalize a URL so that different spellings of the same link compare
+/// equal.
+///
+/// The scheme and host are lowercased, default ports, fragments and
+/// tracking parameters are removed, and trailing slashes are trimmed.
+/// Returns `None` if `raw` is not a valid absolute URL.
+pub fn canonicalize(raw: &str) -> Option<String> {
+    let mut url = Url::parse(raw.trim()).ok()?;
+    url.set_fragment(None);
+
+    let query = url
+        .query_pairs()
+        .filter(|(name, _)| !is_tracking_param(name))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect::<Vec<_>>();
+    if query.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(query);
+    }
+
+    let path = url.path().trim_end_matches('/').to_string();
+    url.set_path(&path);
+
+    let mut canonical = url.to_string();
+    if url.query().is_none() && canonical.ends_with('/') {
+        canonical.pop();
+    }
+    Some(canonical)
+}
+
+/// Return the registrable domain of a URL, for example `github.com` for
+/// `https://www.github.com/rust-lang`.
+///
+/// Without the public suffix list this is an approximation: the last two
+/// labels of the host, or three under a two-letter country code with a
+/// generic second level such as `co.uk`. IP addresses are returned whole.
+/// Returns `None` if `raw` is not a valid absolute URL with a host.
+pub fn domain(raw: &str) -> Option<String> {
+    let url = Url::parse(raw.trim()).ok()?;
+    let host = match url.host()? {
+        Host::Domain(host) => host.trim_end_matches('.'),
+        Host::Ipv4(ip) => return Some(ip.to_string()),
+        Host::Ipv6(ip) => return Some(ip.to_string()),
+    };
+
+    let labels = host.split('.').collect::<Vec<_>>();
+    let keep = match labels.as_slice() {
+        [.., second, country] if country.len() == 2 && COUNTRY_SECOND_LEVELS.contains(second) => 3,
+        _ => 2,
+    };
+    Some(labels[labels.len().saturating_sub(keep)..].join("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonicalize_lowercases_scheme_and_host() {
+        assert_eq!(
+            canonicalize("HTTPS://GitHub.COM/rust-lang/Rust").as_deref(),
+            Some("https://github.com/rust-lang/Rust")
+        );
+    }
+
+    #[test]
+    fn canonicalize_strips_tracking_parameters_and_fragments() {
+        assert_eq!(
+            canonicalize("https://example.com/post?utm_source=hn&id=3&fbclid=x#comments")
+                .as_deref(),
+            Some("https://example.com/post?id=3")
+        );
+        assert_eq!(
+            canonicalize("https://example.com/post?utm_medium=social&gclid=1").as_deref(),
+            Some("https://example.com/post")
+        );
+    }
+
+    #[test]
+    fn canonicalize_trims_trailing_slashes_and_default_ports() {
+        assert_eq!(
+            canonicalize("http://example.com:80/blog/").as_deref(),
+            Some("http://example.com/blog")
+        );
+        assert_eq!(
+            canonicalize(" https://example.com/ ").as_deref(),
+            Some("https://example.com")
+        );
+    }
+
+    #[test]
+    fn canonicalize_rejects_invalid_urls() {
+        assert_eq!(canonicalize(""), None);
+        assert_eq!(canonicalize("/relative/path"), None);
+        assert_eq!(canonicalize("not a url"), None);
+    }
+
+    #[test]
+    fn domain_drops_www_and_other_subdomains() {
+        assert_eq!(
+            domain("https://www.getdropbox.com/u/2/screencast.html").as_deref(),
+            Some("getdropbox.com")
+        );
+        assert_eq!(
+            domain("https://blog.github.com/2021").as_deref(),
+            Some("github.com")
+        );
+        assert_eq!(
+            domain("https://GitHub.com./").as_deref(),
+            Some("github.com")
+        );
+    }
+
+    #[test]
+    fn domain_keeps_country_second_levels() {
+        assert_eq!(
+            domain("https://www.bbc.co.uk/news").as_deref(),
+            Some("bbc.co.uk")
+        );
+        assert_eq!(domain("https://example.de/").as_deref(), Some("example.de"));
+    }
+
+    #[test]
+    fn domain_returns_ip_hosts_whole() {
+        assert_eq!(
+            domain("http://192.168.0.1:8080/").as_deref(),
+            Some("192.168.0.1")
+        );
+        assert_eq!(domain("http://[::1]/").as_deref(), Some("::1"));
+    }
+
+    #[test]
+    fn domain_is_none_without_a_url() {
+        // Text posts have no URL, or an empty one.
+        assert_eq!(domain(""), None);
+        assert_eq!(domain("mailto:pg@example.com"), None);
+    }
+}
@@ -0,0 +1,134 @@
+//! Accounting of which schema fields clients actually request.
+//!
+//! Each request counts once for every field it resolved, whatever the
+//! number of objects the field was resolved on, so the report reads as
+//! "requests using this field". Fields missing from the report have not
+//! been requested since the server started. Fields selected through an
+//! interface are counted under the interface, for example `Item.title`.
+//!
+//! Operation names are chosen by clients, so only the first
+//! `MAX_OPERATIONS` distinct names are tracked; later ones are counted
+//! under `OTHER_OPERATIONS`.
+
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextExecute, NextResolve, ResolveInfo,
+};
+use async_graphql::{QueryPathSegment, Response, ServerResult, Value};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+/// The operation name recorded for anonymous operations.
+pub static ANONYMOUS_OPERATION: &str = "<anonymous>";
+
+/// The operation name recorded for operations past `MAX_OPERATIONS`.
+pub static OTHER_OPERATIONS: &str = "<other>";
+
+/// The number of distinct operation names tracked.
+pub const MAX_OPERATIONS: usize = 100;
+
+/// Usage counts of a single field.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FieldCount {
+    /// The number of requests that resolved the field.
+    pub total: u64,
+    /// The number of requests that resolved the field, by operation name.
+    pub operations: BTreeMap<String, u64>,
+}
+
+/// Field usage counters shared by every request, usable as a schema
+/// extension.
+///
+/// Clones share their counters, so the extension registered with the
+/// schema and the one serving the report see the same data.
+#[derive(Debug, Clone, Default)]
+pub struct FieldUsage {
+    counts: Arc<Mutex<Counts>>,
+}
+
+/// The counters behind `FieldUsage`.
+#[derive(Debug, Default)]
+struct Counts {
+    fields: BTreeMap<String, FieldCount>,
+    operations: HashSet<String>,
+}
+
+impl FieldUsage {
+    /// Create empty counters.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the counts so far, keyed by `Type.field`.
+    pub fn report(&self) -> BTreeMap<String, FieldCount> {
+        self.counts.lock().unwrap().fields.clone()
+    }
+
+    /// Count one request by `operation` using each of `fields`.
+    fn record(&self, operation: &str, fields: HashSet<String>) {
+        let mut counts = self.counts.lock().unwrap();
+        let operation = if counts.operations.contains(operation) {
+            operation
+        } else if counts.operations.len() < MAX_OPERATIONS {
+            counts.operations.insert(operation.to_string());
+            operation
+        } else {
+            OTHER_OPERATIONS
+        };
+        for field in fields {
+            let count = counts.fields.entry(field).or_default();
+            count.total += 1;
+            *count.operations.entry(operation.to_string()).or_default() += 1;
+        }
+    }
+}
+
+impl ExtensionFactory for FieldUsage {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(FieldUsageExtension {
+            usage: self.clone(),
+            fields: Mutex::default(),
+        })
+    }
+}
+
+/// The per-request half of `FieldUsage`, collecting the fields resolved
+/// by one request.
+struct FieldUsageExtension {
+    usage: FieldUsage,
+    fields: Mutex<HashSet<String>>,
+}
+
+#[async_trait::async_trait]
+impl Extension for FieldUsageExtension {
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> Response {
+        let response = next.run(ctx, operation_name).await;
+        let fields = std::mem::take(&mut *self.fields.lock().unwrap());
+        self.usage
+            .record(operation_name.unwrap_or(ANONYMOUS_OPERATION), fields);
+        response
+    }
+
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        // List elements are resolved like fields of the list; only count
+        // real fields, and leave introspection out.
+        let is_field = matches!(info.path_node.segment, QueryPathSegment::Name(_));
+        if is_field && !info.parent_type.starts_with("__") {
+            self.fields
+                .lock()
+                .unwrap()
+                .insert(format!("{}.{}", info.parent_type, info.name));
+        }
+        next.run(ctx, info).await
+    }
+}
@@ -0,0 +1,24 @@
+//! Tests for formatting item ages.
+
+use hn_gql::types::AgeFormat;
+
+#[test]
+fn relative_ages_use_the_largest_whole_unit() {
+    let cases = [
+        (0, "just now"),
+        (59, "just now"),
+        (60, "1 minute ago"),
+        (3 * 3600 + 59, "3 hours ago"),
+        (24 * 3600, "1 day ago"),
+        (45 * 24 * 3600, "1 month ago"),
+        (800 * 24 * 3600, "2 years ago"),
+    ];
+    for &(seconds, expected) in &cases {
+        assert_eq!(AgeFormat::Relative.format(seconds), expected);
+    }
+}
+
+#[test]
+fn seconds_are_printed_as_is() {
+    assert_eq!(AgeFormat::Seconds.format(10800), "10800");
+}
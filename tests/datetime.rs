@@ -0,0 +1,17 @@
+//! Tests for formatting points in time.
+
+use hn_gql::types::DateTime;
+
+#[test]
+fn times_are_printed_as_rfc3339() {
+    assert_eq!(DateTime(1175714200).to_string(), "2007-04-04T19:16:40Z");
+}
+
+#[test]
+fn times_past_year_9999_are_clamped() {
+    assert_eq!(
+        DateTime(253_402_300_799).to_string(),
+        "9999-12-31T23:59:59Z"
+    );
+    assert_eq!(DateTime(u64::MAX).to_string(), "9999-12-31T23:59:59Z");
+}
@@ -0,0 +1,351 @@
+//! Tests for the resolvers hydrating individual items, on synthetic items
+//! replayed from a cassette.
+
+#![cfg(feature = "cassette")]
+
+use async_graphql::Value;
+use hn_gql::build_schema;
+use hn_gql::cassette::Cassette;
+use hn_gql::client::HnClient;
+use hn_gql::schema::HnSchema;
+use serde_json::json;
+
+/// Write `items`, and `documents` at their API paths, into a fresh
+/// cassette named `name`.
+fn cassette(
+    name: &str,
+    items: &[serde_json::Value],
+    documents: &[(&str, serde_json::Value)],
+) -> HnSchema {
+    let dir = std::env::temp_dir().join(format!("hn_gql-items-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(dir.join("item")).unwrap();
+    for item in items {
+        std::fs::write(
+            dir.join(format!("item/{}.json", item["id"])),
+            item.to_string(),
+        )
+        .unwrap();
+    }
+    for (path, document) in documents {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, document.to_string()).unwrap();
+    }
+
+    let client = HnClient::init()
+        .unwrap()
+        .with_cassette(Cassette::replay(dir));
+    build_schema(client).finish()
+}
+
+async fn run(schema: &HnSchema, query: &str) -> serde_json::Value {
+    let data: Value = schema.execute(query).await.into_result().unwrap().data;
+    serde_json::to_value(data).unwrap()
+}
+
+#[tokio::test]
+async fn poll_results_rank_options_by_votes() {
+    let pollopt = |id: u32, score: u32| {
+        json!({"id": id, "type": "pollopt", "by": "pg", "poll": 1, "score": score,
+               "text": format!("Option {}", id), "time": 1000})
+    };
+    let schema = cassette(
+        "poll",
+        &[
+            json!({"id": 1, "type": "poll", "by": "pg", "time": 1000, "title": "A poll",
+                   "score": 5, "parts": [2, 3, 4]}),
+            pollopt(2, 10),
+            pollopt(3, 30),
+            pollopt(4, 0),
+            json!({"id": 5, "type": "poll", "by": "pg", "time": 1000, "title": "A new poll",
+                   "score": 1, "parts": [4]}),
+        ],
+        &[],
+    );
+
+    let query = r#"{
+        item(id: 1) {
+            ... on Poll {
+                totalVotes
+                results {
+                    totalVotes
+                    options { option { id } percentage }
+                    leading { option { id } }
+                }
+            }
+        }
+    }"#;
+    let data = run(&schema, query).await;
+    assert_eq!(data["item"]["totalVotes"], 40);
+    assert_eq!(
+        data["item"]["results"],
+        json!({
+            "totalVotes": 40,
+            "options": [
+                {"option": {"id": "3"}, "percentage": 75.0},
+                {"option": {"id": "2"}, "percentage": 25.0},
+                {"option": {"id": "4"}, "percentage": 0.0},
+            ],
+            "leading": {"option": {"id": "3"}},
+        })
+    );
+
+    // Nobody has voted yet, so nothing leads.
+    let query =
+        "{ item(id: 5) { ... on Poll { results { leading { option { id } } totalVotes } } } }";
+    let data = run(&schema, query).await;
+    assert_eq!(
+        data["item"]["results"],
+        json!({"leading": null, "totalVotes": 0})
+    );
+}
+
+#[tokio::test]
+async fn items_after_pages_up_to_the_max_item() {
+    let story = |id: u32| {
+        json!({"id": id, "type": "story", "by": "pg", "time": 1000, "title": "A story",
+               "score": 1})
+    };
+    // Item 3 failed to load.
+    let schema = cassette(
+        "after",
+        &[story(1), story(2), story(4), story(5)],
+        &[("maxitem.json", json!(5))],
+    );
+
+    let data = run(&schema, "{ itemsAfter(id: 1) { id } }").await;
+    assert_eq!(
+        data["itemsAfter"],
+        json!([{"id": "2"}, {"id": "4"}, {"id": "5"}])
+    );
+
+    let data = run(&schema, "{ itemsAfter(id: 1, limit: 2) { id } }").await;
+    assert_eq!(data["itemsAfter"], json!([{"id": "2"}]));
+
+    // Caught up.
+    let data = run(&schema, "{ itemsAfter(id: 5) { id } }").await;
+    assert_eq!(data["itemsAfter"], json!([]));
+}
+
+#[tokio::test]
+async fn items_line_up_with_the_ids_asked_for() {
+    let schema = cassette(
+        "batch",
+        &[
+            json!({"id": 1, "type": "story", "by": "pg", "time": 1000, "title": "A story",
+                   "score": 1, "kids": [2]}),
+            json!({"id": 2, "type": "comment", "by": "jl", "time": 1030, "parent": 1,
+                   "text": "A comment"}),
+        ],
+        &[],
+    );
+
+    let query = "{ items(ids: [2, 3, 1, 2]) { id __typename } }";
+    let data = run(&schema, query).await;
+    assert_eq!(
+        data["items"],
+        json!([
+            {"id": "2", "__typename": "Comment"},
+            null,
+            {"id": "1", "__typename": "Story"},
+            {"id": "2", "__typename": "Comment"},
+        ])
+    );
+
+    let data = run(&schema, "{ items(ids: []) { id } }").await;
+    assert_eq!(data["items"], json!([]));
+}
+
+#[tokio::test]
+async fn nodes_round_trip_through_their_global_ids() {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    let schema = cassette(
+        "node",
+        &[
+            json!({"id": 1, "type": "story", "by": "jl", "time": 1000, "title": "A story",
+                 "score": 1}),
+        ],
+        &[(
+            "user/jl.json",
+            json!({"id": "jl", "created": 1000, "karma": 10}),
+        )],
+    );
+
+    let data = run(
+        &schema,
+        "{ item(id: 1) { ... on Story { nodeId authorUser { nodeId } } } }",
+    )
+    .await;
+    let story_id = data["item"]["nodeId"].as_str().unwrap().to_string();
+    let user_id = data["item"]["authorUser"]["nodeId"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    assert_eq!(story_id, STANDARD.encode("Story:1"));
+    assert_eq!(user_id, STANDARD.encode("User:jl"));
+
+    let query = format!(
+        r#"{{ story: node(id: "{}") {{ __typename nodeId ... on Story {{ id }} }}
+             user: node(id: "{}") {{ __typename ... on User {{ id }} }} }}"#,
+        story_id, user_id
+    );
+    let data = run(&schema, &query).await;
+    assert_eq!(
+        data,
+        json!({
+            "story": {"__typename": "Story", "nodeId": story_id, "id": "1"},
+            "user": {"__typename": "User", "id": "jl"},
+        })
+    );
+
+    // Ids naming an item under the wrong type, missing items and malformed
+    // ids name nothing.
+    for id in [
+        STANDARD.encode("Comment:1"),
+        STANDARD.encode("Story:2"),
+        STANDARD.encode("User:not valid"),
+        "not base64".to_string(),
+    ] {
+        let data = run(
+            &schema,
+            &format!(r#"{{ node(id: "{}") {{ nodeId }} }}"#, id),
+        )
+        .await;
+        assert_eq!(data["node"], json!(null), "{}", id);
+    }
+}
+
+#[tokio::test]
+async fn canonical_story_merges_submissions_of_a_url() {
+    let story = |id: u32, url: &str, score: u32| {
+        json!({"id": id, "type": "story", "by": "pg", "time": 1000, "title": "A story",
+               "score": score, "url": url})
+    };
+    let hits = |ids: &[u32]| {
+        let hits = ids
+            .iter()
+            .map(|id| json!({"objectID": id.to_string()}))
+            .collect::<Vec<_>>();
+        json!({ "hits": hits })
+    };
+    // HN Search matches fuzzily: story 3 links elsewhere and 4 is a job.
+    let schema = cassette(
+        "canonical",
+        &[
+            story(1, "https://example.com/post/?utm_source=hn", 5),
+            story(2, "https://EXAMPLE.com/post#comments", 20),
+            story(3, "https://example.com/other", 100),
+            json!({"id": 4, "type": "job", "by": "pg", "time": 1000, "title": "A job",
+                   "score": 1, "url": "https://example.com/post"}),
+        ],
+        &[
+            (
+                "search/url-https___example_com_post-50.json",
+                hits(&[1, 2, 3, 4]),
+            ),
+            ("search/url-https___example_com_none-50.json", hits(&[])),
+        ],
+    );
+
+    let query = r#"{ canonicalStory(url: "https://example.com/post") {
+        story { id }
+        otherSubmissions { id }
+    } }"#;
+    let data = run(&schema, query).await;
+    assert_eq!(
+        data["canonicalStory"],
+        json!({"story": {"id": "2"}, "otherSubmissions": [{"id": "1"}]})
+    );
+
+    let data = run(
+        &schema,
+        r#"{ canonicalStory(url: "https://example.com/none") { story { id } } }"#,
+    )
+    .await;
+    assert_eq!(data["canonicalStory"], json!(null));
+
+    // Not a URL, so upstream isn't asked.
+    let data = run(
+        &schema,
+        r#"{ canonicalStory(url: "not a url") { story { id } } }"#,
+    )
+    .await;
+    assert_eq!(data["canonicalStory"], json!(null));
+}
+
+#[tokio::test]
+async fn submissions_filter_by_type_across_pages() {
+    let story = |id: u32, dead: bool| {
+        json!({"id": id, "type": "story", "by": "jl", "time": 1000, "title": "A story",
+               "score": 1, "dead": dead})
+    };
+    let comment = |id: u32| {
+        json!({"id": id, "type": "comment", "by": "jl", "time": 1000, "parent": 5,
+               "text": "A comment"})
+    };
+    let schema = cassette(
+        "submitted",
+        &[
+            story(5, false),
+            comment(4),
+            story(3, true),
+            comment(2),
+            story(1, false),
+        ],
+        &[(
+            "user/jl.json",
+            json!({"id": "jl", "created": 1000, "karma": 10, "submitted": [5, 4, 3, 2, 1]}),
+        )],
+    );
+    let page = |args: &str| {
+        format!(
+            r#"{{ user(id: "jl") {{ submittedConnection({}) {{
+                edges {{ cursor node {{ id }} }}
+                pageInfo {{ hasNextPage endCursor }}
+                totalCount
+            }} }} }}"#,
+            args
+        )
+    };
+    let edges = |data: &serde_json::Value| {
+        let connection = &data["user"]["submittedConnection"];
+        let edges = connection["edges"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|edge| {
+                (
+                    edge["cursor"].as_str().unwrap().to_string(),
+                    edge["node"]["id"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect::<Vec<_>>();
+        (
+            edges,
+            connection["pageInfo"].clone(),
+            connection["totalCount"].clone(),
+        )
+    };
+    let edge = |cursor: &str, id: &str| (cursor.to_string(), id.to_string());
+
+    // The dead story is skipped; cursors keep positions in `submitted`.
+    let (found, page_info, total) = edges(&run(&schema, &page("type: STORY")).await);
+    assert_eq!(found, [edge("0", "5"), edge("4", "1")]);
+    assert_eq!(page_info, json!({"hasNextPage": false, "endCursor": "4"}));
+    assert_eq!(total, 5);
+
+    let (found, page_info, _) = edges(&run(&schema, &page("type: STORY, first: 1")).await);
+    assert_eq!(found, [edge("0", "5")]);
+    assert_eq!(page_info, json!({"hasNextPage": true, "endCursor": "0"}));
+    let query = page(r#"type: STORY, first: 1, after: "0""#);
+    let (found, _, _) = edges(&run(&schema, &query).await);
+    assert_eq!(found, [edge("4", "1")]);
+
+    let query = page("type: STORY, includeDead: true");
+    let (found, _, _) = edges(&run(&schema, &query).await);
+    assert_eq!(found, [edge("0", "5"), edge("2", "3"), edge("4", "1")]);
+
+    let (found, _, _) = edges(&run(&schema, &page("type: COMMENT")).await);
+    assert_eq!(found, [edge("1", "4"), edge("3", "2")]);
+}
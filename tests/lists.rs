@@ -0,0 +1,142 @@
+//! Tests for the resolvers ranking the top stories and their comments, on
+//! a synthetic top list replayed from a cassette.
+
+#![cfg(feature = "cassette")]
+
+use async_graphql::Value;
+use hn_gql::cassette::Cassette;
+use hn_gql::client::HnClient;
+use hn_gql::connection::PageLimits;
+use hn_gql::schema::HnSchema;
+use hn_gql::{build_schema, SchemaBuilderExt};
+use serde_json::json;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Write three top stories submitted an hour ago into a fresh cassette
+/// named `name`, with comments posted since:
+///
+/// ```text
+/// 1 story          2 story          3 story
+/// └── 11 (1 reply) ├── 21           └── 31 (2 replies)
+///                  └── 22
+/// ```
+///
+/// Pages hold at most `max_page` entries.
+fn top_list(name: &str, max_page: usize) -> HnSchema {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let story = |id: u32, score: u32, kids: &[u32]| {
+        json!({"id": id, "type": "story", "by": "pg", "time": now - 3600,
+               "title": format!("Story {}", id), "score": score,
+               "descendants": kids.len(), "kids": kids})
+    };
+    let comment = |id: u32, parent: u32, kids: &[u32]| {
+        json!({"id": id, "type": "comment", "by": "jl", "time": now - 60,
+               "parent": parent, "text": "A comment", "kids": kids})
+    };
+    let items = [
+        story(1, 10, &[11]),
+        story(2, 20, &[21, 22]),
+        story(3, 30, &[31]),
+        comment(11, 1, &[12]),
+        comment(12, 11, &[]),
+        comment(21, 2, &[]),
+        comment(22, 2, &[]),
+        comment(31, 3, &[32, 33]),
+        comment(32, 31, &[]),
+        comment(33, 31, &[]),
+    ];
+
+    let dir = std::env::temp_dir().join(format!("hn_gql-lists-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(dir.join("item")).unwrap();
+    std::fs::write(dir.join("topstories.json"), "[1, 2, 3]").unwrap();
+    for item in &items {
+        std::fs::write(
+            dir.join(format!("item/{}.json", item["id"])),
+            item.to_string(),
+        )
+        .unwrap();
+    }
+
+    let client = HnClient::init()
+        .unwrap()
+        .with_cassette(Cassette::replay(dir));
+    build_schema(client)
+        .page_limits(PageLimits {
+            max: max_page,
+            kids_default: None,
+        })
+        .finish()
+}
+
+async fn run(schema: &HnSchema, query: &str) -> serde_json::Value {
+    let data: Value = schema.execute(query).await.into_result().unwrap().data;
+    serde_json::to_value(data).unwrap()
+}
+
+#[tokio::test]
+async fn ranked_lists_are_capped_at_the_page_limit() {
+    let schema = top_list("capped", 2);
+
+    let data = run(&schema, "{ frontPage(limit: 50) { id } }").await;
+    assert_eq!(data["frontPage"].as_array().unwrap().len(), 2);
+
+    let data = run(&schema, "{ bestComments(limit: 50) { id } }").await;
+    assert_eq!(data["bestComments"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn best_comments_rank_by_replies_then_id() {
+    let schema = top_list("best", 500);
+
+    let data = run(&schema, "{ bestComments { id } }").await;
+    assert_eq!(
+        data["bestComments"],
+        json!([{"id": "31"}, {"id": "11"}, {"id": "21"}, {"id": "22"}])
+    );
+
+    let data = run(&schema, "{ bestComments(offset: 1, limit: 2) { id } }").await;
+    assert_eq!(data["bestComments"], json!([{"id": "11"}, {"id": "21"}]));
+
+    // Nothing was posted in the last ten seconds.
+    let data = run(&schema, r#"{ bestComments(window: "10s") { id } }"#).await;
+    assert_eq!(data["bestComments"], json!([]));
+}
+
+#[tokio::test]
+async fn front_page_orders_stories_by_strategy() {
+    let schema = top_list("front", 500);
+    let ids = |data: &serde_json::Value| {
+        data["frontPage"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|story| story["id"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    let data = run(&schema, "{ frontPage { id } }").await;
+    assert_eq!(ids(&data), ["3", "2", "1"]);
+
+    let data = run(
+        &schema,
+        "{ frontPage(strategy: SCORE_ONLY, offset: 1, limit: 1) { id } }",
+    )
+    .await;
+    assert_eq!(ids(&data), ["2"]);
+
+    // Story 2 has the most comments; the others tie and keep their order.
+    let data = run(&schema, "{ frontPage(strategy: COMMENT_VELOCITY) { id } }").await;
+    assert_eq!(ids(&data), ["2", "1", "3"]);
+
+    let query = "{ frontPage(strategy: CUSTOM, weights: {score: 0, comments: 1}) { id } }";
+    let data = run(&schema, query).await;
+    assert_eq!(ids(&data), ["2", "1", "3"]);
+
+    // Weights only apply to the custom strategy.
+    let query = "{ frontPage(strategy: HN_GRAVITY, weights: {score: 0, comments: 1}) { id } }";
+    let data = run(&schema, query).await;
+    assert_eq!(ids(&data), ["3", "2", "1"]);
+}
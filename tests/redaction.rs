@@ -0,0 +1,224 @@
+//! Tests for the response redaction extension.
+
+use async_graphql::{value, EmptyMutation, EmptySubscription, Object, Schema, SimpleObject};
+use hn_gql::redaction::{Redaction, RedactionMode};
+
+#[derive(SimpleObject)]
+struct Story {
+    by: String,
+    title: String,
+    kids: Vec<String>,
+}
+
+#[derive(SimpleObject)]
+struct User {
+    id: String,
+    node_id: String,
+    about: Option<String>,
+    karma: i32,
+    is_moderator: bool,
+}
+
+#[derive(SimpleObject)]
+struct UnknownItem {
+    raw: String,
+}
+
+struct Query;
+
+#[Object]
+impl Query {
+    async fn story(&self) -> Story {
+        Story {
+            by: "pg".to_string(),
+            title: "Poll: What would happen if News.YC had explicit support for polls?".to_string(),
+            kids: vec!["pg".to_string(), "dhouston".to_string()],
+        }
+    }
+
+    async fn user(&self) -> User {
+        User {
+            id: "pg".to_string(),
+            // The global id of `User:pg`.
+            node_id: "VXNlcjpwZw==".to_string(),
+            about: Some("Bug fixer.".to_string()),
+            karma: 155040,
+            is_moderator: false,
+        }
+    }
+
+    async fn unknown(&self) -> UnknownItem {
+        UnknownItem {
+            raw: r#"{"by":"pg","type":"event"}"#.to_string(),
+        }
+    }
+}
+
+async fn execute(redaction: Redaction, query: &str) -> async_graphql::Response {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .extension(redaction)
+        .finish()
+        .execute(query)
+        .await
+}
+
+async fn run(redaction: Redaction, query: &str) -> async_graphql::Value {
+    execute(redaction, query).await.into_result().unwrap().data
+}
+
+#[tokio::test]
+async fn blanked_fields_are_emptied() {
+    let redaction = Redaction::new()
+        .field("User", "about", RedactionMode::Blank)
+        .field("Story", "kids", RedactionMode::Blank);
+
+    assert_eq!(
+        run(redaction, "{ story { by kids } user { id about } }").await,
+        value!({
+            "story": { "by": "pg", "kids": [] },
+            "user": { "id": "pg", "about": "" },
+        })
+    );
+}
+
+#[tokio::test]
+async fn hashed_fields_stay_comparable() {
+    let redaction = Redaction::new()
+        .salt("s3cret")
+        .field("*", "by", RedactionMode::Hash)
+        .field("*", "id", RedactionMode::Hash)
+        .field("Story", "kids", RedactionMode::Hash);
+
+    let data = run(redaction, "{ story { by title kids } user { id } }").await;
+    let data = serde_json::to_value(data).unwrap();
+    let by = data["story"]["by"].as_str().unwrap();
+
+    assert_ne!(by, "pg");
+    assert_eq!(by.len(), 16);
+    assert_eq!(data["user"]["id"], by);
+    assert_eq!(data["story"]["kids"][0], by);
+    assert_ne!(data["story"]["kids"][1], by);
+    assert_eq!(
+        data["story"]["title"],
+        "Poll: What would happen if News.YC had explicit support for polls?"
+    );
+}
+
+#[tokio::test]
+async fn the_salt_changes_hashes() {
+    let query = "{ story { by } }";
+    let a = run(
+        Redaction::new().field("Story", "by", RedactionMode::Hash),
+        query,
+    )
+    .await;
+    let b = run(
+        Redaction::new()
+            .salt("other")
+            .field("Story", "by", RedactionMode::Hash),
+        query,
+    )
+    .await;
+
+    assert_ne!(a, b);
+}
+
+#[tokio::test]
+async fn redacted_values_keep_their_type() {
+    let redaction = Redaction::new()
+        .field("User", "karma", RedactionMode::Hash)
+        .field("User", "isModerator", RedactionMode::Blank);
+    let data = run(redaction, "{ user { karma isModerator } }").await;
+    let data = serde_json::to_value(data).unwrap();
+
+    assert!(data["user"]["karma"].is_i64());
+    assert_ne!(data["user"]["karma"], 155040);
+    assert_eq!(data["user"]["isModerator"], false);
+
+    let redaction = Redaction::new().field("User", "karma", RedactionMode::Blank);
+    assert_eq!(
+        run(redaction, "{ user { karma } }").await,
+        value!({ "user": { "karma": 0 } })
+    );
+
+    let redaction = Redaction::new().field("User", "isModerator", RedactionMode::Hash);
+    assert!(execute(redaction, "{ user { isModerator } }")
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn node_ids_follow_redacted_ids() {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+
+    let redaction = Redaction::new().field("User", "id", RedactionMode::Hash);
+    let data = run(redaction, "{ user { id nodeId } }").await;
+    let data = serde_json::to_value(data).unwrap();
+    let node_id = STANDARD
+        .decode(data["user"]["nodeId"].as_str().unwrap())
+        .unwrap();
+
+    assert_eq!(
+        String::from_utf8(node_id).unwrap(),
+        format!("User:{}", data["user"]["id"].as_str().unwrap())
+    );
+}
+
+#[tokio::test]
+async fn raw_items_are_blanked_by_any_rule() {
+    let query = "{ unknown { raw } }";
+    assert_eq!(
+        run(
+            Redaction::new().field("User", "about", RedactionMode::Blank),
+            query
+        )
+        .await,
+        value!({ "unknown": { "raw": "" } })
+    );
+    assert_eq!(
+        run(Redaction::new(), query).await,
+        value!({ "unknown": { "raw": r#"{"by":"pg","type":"event"}"# } })
+    );
+}
+
+#[cfg(feature = "cassette")]
+#[tokio::test]
+async fn fields_selected_on_interfaces_are_redacted() {
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use hn_gql::build_schema;
+    use hn_gql::cassette::Cassette;
+    use hn_gql::client::HnClient;
+    use std::path::Path;
+
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+    let client = HnClient::init()
+        .unwrap()
+        .with_cassette(Cassette::replay(dir));
+    let schema = build_schema(client)
+        .extension(
+            Redaction::new()
+                .field("Story", "id", RedactionMode::Hash)
+                .field("Story", "author", RedactionMode::Hash),
+        )
+        .finish();
+
+    // The global id of `Story:8863`.
+    let query = r#"{
+        item(id: 8863) { id author }
+        node(id: "U3Rvcnk6ODg2Mw==") { nodeId }
+        story: item(id: 8863) { ... on Story { id nodeId } }
+    }"#;
+    let data = schema.execute(query).await.into_result().unwrap().data;
+    let data = serde_json::to_value(data).unwrap();
+
+    let id = data["story"]["id"].as_str().unwrap();
+    assert_ne!(id, "8863");
+    assert_eq!(data["item"]["id"], id);
+    assert_ne!(data["item"]["author"], "dhouston");
+    assert_eq!(data["node"]["nodeId"], data["story"]["nodeId"]);
+
+    let node_id = STANDARD
+        .decode(data["node"]["nodeId"].as_str().unwrap())
+        .unwrap();
+    assert_eq!(String::from_utf8(node_id).unwrap(), format!("Story:{}", id));
+}
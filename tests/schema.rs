@@ -0,0 +1,50 @@
+//! Golden-file test for the GraphQL schema.
+//!
+//! Any change to the schema must be accompanied by an update to
+//! `tests/schema.graphql`, so that it shows up explicitly in review.
+//! Regenerate the file with `UPDATE_SCHEMA=1 cargo test --test schema`.
+
+use hn_gql::build_schema;
+use hn_gql::client::HnClient;
+use std::path::Path;
+
+#[test]
+#[cfg_attr(
+    not(feature = "job-parsing"),
+    ignore = "the golden schema is generated with default features"
+)]
+fn schema_matches_golden_file() {
+    let schema = build_schema(HnClient::init().unwrap()).finish();
+    let sdl = normalize(&schema.sdl());
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/schema.graphql");
+
+    if std::env::var_os("UPDATE_SCHEMA").is_some() {
+        std::fs::write(&path, &sdl).unwrap();
+        return;
+    }
+
+    let golden = std::fs::read_to_string(&path).unwrap_or_default();
+    assert!(
+        golden == sdl,
+        "the schema differs from tests/schema.graphql; \
+         rerun with UPDATE_SCHEMA=1 if the change is intended\n\n{}",
+        sdl
+    );
+}
+
+/// Sort the interfaces in `implements` clauses, which async-graphql emits
+/// in no particular order.
+fn normalize(sdl: &str) -> String {
+    sdl.lines()
+        .map(|line| match line.split_once(" implements ") {
+            Some((head, rest)) if head.starts_with("type ") => {
+                let rest = rest.trim_end_matches(" {");
+                let mut interfaces = rest.split(" & ").collect::<Vec<_>>();
+                interfaces.sort_unstable();
+                format!("{} implements {} {{", head, interfaces.join(" & "))
+            }
+            _ => line.to_string(),
+        })
+        .map(|line| line + "\n")
+        .collect()
+}
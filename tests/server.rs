@@ -0,0 +1,237 @@
+//! Tests for the server lifecycle.
+
+use hn_gql::build_schema;
+use hn_gql::client::HnClient;
+use hn_gql::server::Server;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+#[tokio::test]
+async fn hooks_run_around_serving() {
+    let client = HnClient::init().unwrap();
+    let schema = build_schema(client.clone()).finish();
+    let started = Arc::new(Mutex::new(None));
+    let stopped = Arc::new(AtomicBool::new(false));
+
+    let server = Server::builder(schema, client)
+        .addr(([127, 0, 0, 1], 0))
+        .on_startup({
+            let started = started.clone();
+            move |addr| *started.lock().unwrap() = Some(addr)
+        })
+        .on_shutdown({
+            let stopped = stopped.clone();
+            move || stopped.store(true, Ordering::SeqCst)
+        })
+        .start()
+        .unwrap();
+    assert_eq!(*started.lock().unwrap(), Some(server.addr()));
+
+    let body = reqwest::Client::new()
+        .post(format!("http://{}/", server.addr()))
+        .json(&serde_json::json!({ "query": "{ __typename }" }))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(body, r#"{"data":{"__typename":"Query"}}"#);
+
+    assert!(!stopped.load(Ordering::SeqCst));
+    let addr = server.addr();
+    server.shutdown().await;
+    assert!(stopped.load(Ordering::SeqCst));
+    assert!(std::net::TcpStream::connect(addr).is_err());
+}
+
+#[tokio::test]
+async fn the_mirror_can_be_turned_off() {
+    let client = HnClient::init().unwrap();
+    let schema = build_schema(client.clone()).finish();
+    let server = Server::builder(schema, client)
+        .addr(([127, 0, 0, 1], 0))
+        .mirror(false)
+        .start()
+        .unwrap();
+
+    // Without the mirror, the path falls through to the GraphQL route.
+    let body = reqwest::get(format!("http://{}/v0/topstories.json", server.addr()))
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert!(body.get("errors").is_some());
+
+    server.shutdown().await;
+}
+
+#[cfg(feature = "cassette")]
+#[tokio::test]
+async fn loader_stats_count_cache_hits() {
+    use hn_gql::cassette::Cassette;
+    use hn_gql::stats::LOADER_STATS_HEADER;
+    use serde_json::json;
+
+    // A synthetic story and a comment on it.
+    let dir = std::env::temp_dir().join(format!("hn_gql-server-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("item")).unwrap();
+    let items = [
+        json!({"id": 100, "type": "story", "by": "pg", "time": 1000, "title": "A story",
+               "score": 10, "descendants": 1, "kids": [101]}),
+        json!({"id": 101, "type": "comment", "by": "jl", "time": 1030, "parent": 100,
+               "text": "First"}),
+    ];
+    for item in &items {
+        let path = dir.join(format!("item/{}.json", item["id"]));
+        std::fs::write(path, item.to_string()).unwrap();
+    }
+
+    let client = HnClient::init()
+        .unwrap()
+        .with_cassette(Cassette::replay(dir));
+    let schema = build_schema(client.clone()).finish();
+    let server = Server::builder(schema, client)
+        .addr(([127, 0, 0, 1], 0))
+        .start()
+        .unwrap();
+
+    // The story is loaded first, then again as the comment's parent.
+    let query = "{ items(ids: [100, 101]) { id ... on Comment { parent { id } } } }";
+    let body = reqwest::Client::new()
+        .post(format!("http://{}/", server.addr()))
+        .header(LOADER_STATS_HEADER, "1")
+        .json(&json!({ "query": query }))
+        .send()
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["items"][1]["parent"]["id"], "100");
+    assert_eq!(body["extensions"]["loaderStats"]["cacheHits"], 1);
+    assert_eq!(body["extensions"]["loaderStats"]["batches"], 1);
+
+    server.shutdown().await;
+}
+
+#[cfg(feature = "cassette")]
+#[tokio::test]
+async fn requests_are_held_to_their_class_budget() {
+    use hn_gql::cassette::Cassette;
+    use hn_gql::stats::{BudgetClasses, BUDGET_CLASS_HEADER, COST_HEADER};
+    use serde_json::json;
+
+    let dir = std::env::temp_dir().join(format!("hn_gql-budget-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("item")).unwrap();
+    for id in 100..103 {
+        let item = json!({"id": id, "type": "story", "by": "pg", "time": 1000,
+                          "title": "A story", "score": 10});
+        std::fs::write(dir.join(format!("item/{}.json", id)), item.to_string()).unwrap();
+    }
+
+    let client = HnClient::init()
+        .unwrap()
+        .with_cassette(Cassette::replay(dir));
+    let schema = build_schema(client.clone()).finish();
+    let server = Server::builder(schema, client)
+        .addr(([127, 0, 0, 1], 0))
+        .budget_classes(BudgetClasses::new(1000).class("small", 2).class("tiny", 1))
+        .start()
+        .unwrap();
+    let post = |class: &'static str| {
+        reqwest::Client::new()
+            .post(format!("http://{}/", server.addr()))
+            .header(BUDGET_CLASS_HEADER, class)
+            .json(&json!({ "query": "{ items(ids: [100, 101, 102]) { id } }" }))
+            .send()
+    };
+
+    // Three items fit the default budget.
+    let body = post("unknown")
+        .await
+        .unwrap()
+        .json::<serde_json::Value>()
+        .await
+        .unwrap();
+    assert_eq!(body["data"]["items"].as_array().unwrap().len(), 3);
+    assert!(body.get("errors").is_none());
+
+    // The query passes the complexity check but runs out of calls.
+    let response = post("small").await.unwrap();
+    assert_eq!(response.headers()[COST_HEADER], "2");
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    let fetched = body["data"]["items"].as_array().unwrap();
+    assert_eq!(fetched.iter().filter(|item| item.is_null()).count(), 1);
+    assert_eq!(
+        body["errors"][0]["message"],
+        "upstream call budget of 2 calls exceeded"
+    );
+    assert_eq!(body["extensions"]["upstreamBudgetExceeded"], true);
+
+    // Too complex to fit at all, so it never runs.
+    let response = post("tiny").await.unwrap();
+    assert_eq!(response.headers()[COST_HEADER], "0");
+    let body = response.json::<serde_json::Value>().await.unwrap();
+    assert!(body["data"].is_null());
+    assert_eq!(
+        body["errors"][0]["message"],
+        "query complexity 2 exceeds the upstream call budget of 1 calls"
+    );
+
+    server.shutdown().await;
+}
+
+#[cfg(feature = "cassette")]
+#[tokio::test]
+async fn the_mirror_passes_upstream_documents_through() {
+    use hn_gql::cassette::Cassette;
+    use serde_json::json;
+
+    // Fields the schema doesn't know about are passed through too.
+    let item = json!({"id": 100, "type": "story", "by": "pg", "time": 1000,
+                      "title": "A story", "score": 10, "unknownField": [1, 2]});
+    let dir = std::env::temp_dir().join(format!("hn_gql-mirror-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("item")).unwrap();
+    std::fs::write(dir.join("item/100.json"), item.to_string()).unwrap();
+    std::fs::write(dir.join("topstories.json"), "[100]").unwrap();
+
+    let client = HnClient::init()
+        .unwrap()
+        .with_cassette(Cassette::replay(dir));
+    let schema = build_schema(client.clone()).finish();
+    let server = Server::builder(schema, client)
+        .addr(([127, 0, 0, 1], 0))
+        .start()
+        .unwrap();
+    let get = |path: &str| reqwest::get(format!("http://{}/v0/{}", server.addr(), path));
+
+    let response = get("item/100.json").await.unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(response.json::<serde_json::Value>().await.unwrap(), item);
+
+    let response = get("topstories.json").await.unwrap();
+    assert_eq!(
+        response.json::<serde_json::Value>().await.unwrap(),
+        json!([100])
+    );
+
+    // Documents upstream couldn't serve are reported as a bad gateway.
+    let response = get("item/101.json").await.unwrap();
+    assert_eq!(response.status(), 502);
+
+    // Paths the Firebase API doesn't serve never reach upstream, and fall
+    // through to the GraphQL route.
+    for path in ["item/abc.json", "secrets.json", "user/.json"] {
+        let body = get(path)
+            .await
+            .unwrap()
+            .json::<serde_json::Value>()
+            .await
+            .unwrap();
+        assert!(body.get("errors").is_some(), "{}", path);
+    }
+
+    server.shutdown().await;
+}
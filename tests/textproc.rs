@@ -0,0 +1,104 @@
+//! Tests for the HTML processors, on text shaped like HN's.
+
+use hn_gql::textproc::{process_all, Links, Markdown, PlainText, Processor, Quotes, Sanitize};
+
+static COMMENT: &str = "&gt; Is it fast?<p>Yes, see <a href=\"https:&#x2F;&#x2F;example.com&#x2F;bench\" rel=\"nofollow\">https:&#x2F;&#x2F;example.com&#x2F;bench</a>. It&#x27;s <i>very</i> fast.<p><pre><code>  cargo bench\n</code></pre>";
+
+#[test]
+fn plain_text_strips_tags_and_decodes_entities() {
+    assert_eq!(
+        PlainText.process(COMMENT),
+        "> Is it fast?\n\nYes, see https://example.com/bench. It's very fast.\n\n  cargo bench"
+    );
+    assert_eq!(PlainText.process("1 < 2 <b"), "1 < 2 <b");
+}
+
+#[test]
+fn links_are_extracted_in_order() {
+    assert_eq!(Links.process(COMMENT), vec!["https://example.com/bench"]);
+    assert!(Links.process("no links here").is_empty());
+}
+
+#[test]
+fn link_targets_are_read_however_they_are_quoted() {
+    let html = concat!(
+        "<a data-href=\"https://example.com/data\" href=\"https://example.com/1\">1</a>",
+        "<a xhref=\"https://example.com/x\">x</a>",
+        "<a rel=nofollow href='https://example.com/2'>2</a>",
+        "<a HREF = https://example.com/3>3</a>",
+        "<a title=\"href=https://example.com/t\" href=\"https://example.com/4\">4</a>",
+        "<a href>none</a>",
+    );
+    assert_eq!(
+        Links.process(html),
+        vec![
+            "https://example.com/1",
+            "https://example.com/2",
+            "https://example.com/3",
+            "https://example.com/4",
+        ]
+    );
+}
+
+#[test]
+fn markdown_keeps_emphasis_links_and_code() {
+    assert_eq!(
+        Markdown.process(COMMENT),
+        "> Is it fast?\n\nYes, see <https://example.com/bench>. It's *very* fast.\n\n```\n  cargo bench\n```"
+    );
+}
+
+#[test]
+fn markdown_escapes_text_and_unsafe_links() {
+    assert_eq!(
+        Markdown.process("a &lt;script&gt; &amp; *b* [c](d) # e"),
+        "a \\<script\\> \\& \\*b\\* \\[c\\](d) \\# e"
+    );
+    assert_eq!(Markdown.process("x &gt; y"), "x \\> y");
+    assert_eq!(
+        Markdown.process("<a href=\"javascript:alert(1)\">click</a> me"),
+        "click me"
+    );
+    assert_eq!(
+        Markdown.process("<a href=\"https://example.com/a b&gt;\">e</a>"),
+        "<https://example.com/a%20b%3E>"
+    );
+    assert_eq!(
+        Markdown.process("<pre><code>```\n*x*\n</code></pre>"),
+        "````\n```\n*x*\n````"
+    );
+}
+
+#[test]
+fn quotes_are_paragraphs_starting_with_a_marker() {
+    assert_eq!(Quotes.process(COMMENT), vec!["Is it fast?"]);
+    assert!(Quotes.process("a &gt; b").is_empty());
+}
+
+#[test]
+fn sanitize_drops_unknown_tags_and_unsafe_links() {
+    assert_eq!(
+        Sanitize.process(
+            "<p onclick=\"x()\">hi <script>bad()</script><a href=\"javascript:bad()\">x</a>"
+        ),
+        "<p>hi bad()<a>x</a>"
+    );
+    assert_eq!(
+        Sanitize.process("<a href=\"https:&#x2F;&#x2F;example.com\">e</a>"),
+        "<a href=\"https://example.com\" rel=\"nofollow\">e</a>"
+    );
+}
+
+#[tokio::test]
+async fn process_all_keeps_input_order() {
+    let fragments = (0..1000)
+        .map(|n| format!("<p>comment <i>{}</i>", n))
+        .collect::<Vec<_>>();
+    let expected = fragments
+        .iter()
+        .map(|html| Markdown.process(html))
+        .collect::<Vec<_>>();
+
+    assert_eq!(process_all(Markdown, fragments).await, expected);
+    assert!(process_all(PlainText, Vec::new()).await.is_empty());
+}
@@ -0,0 +1,125 @@
+//! Tests for the resolvers walking comment threads, on a synthetic thread
+//! replayed from a cassette.
+
+#![cfg(feature = "cassette")]
+
+use async_graphql::Value;
+use hn_gql::build_schema;
+use hn_gql::cassette::Cassette;
+use hn_gql::client::HnClient;
+use hn_gql::schema::HnSchema;
+use serde_json::json;
+use std::path::PathBuf;
+
+/// Write a story submitted at time 1000 with four comments into a fresh
+/// cassette named `name`:
+///
+/// ```text
+/// 100 story
+/// ├── 101 at +30s
+/// │   └── 104 at +3650s
+/// ├── 102 at +3700s
+/// └── 103 at +7300s
+/// ```
+fn thread(name: &str) -> HnSchema {
+    let items = [
+        json!({"id": 100, "type": "story", "by": "pg", "time": 1000, "title": "A story",
+               "score": 10, "descendants": 4, "kids": [101, 102, 103]}),
+        json!({"id": 101, "type": "comment", "by": "jl", "time": 1030, "parent": 100,
+               "text": "First", "kids": [104]}),
+        json!({"id": 102, "type": "comment", "by": "jl", "time": 4700, "parent": 100,
+               "text": "Second"}),
+        json!({"id": 103, "type": "comment", "by": "jl", "time": 8300, "parent": 100,
+               "text": "Third"}),
+        json!({"id": 104, "type": "comment", "by": "pg", "time": 4650, "parent": 101,
+               "text": "Reply"}),
+    ];
+
+    let dir: PathBuf =
+        std::env::temp_dir().join(format!("hn_gql-thread-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(dir.join("item")).unwrap();
+    for item in &items {
+        std::fs::write(
+            dir.join(format!("item/{}.json", item["id"])),
+            item.to_string(),
+        )
+        .unwrap();
+    }
+
+    let client = HnClient::init()
+        .unwrap()
+        .with_cassette(Cassette::replay(dir));
+    build_schema(client).finish()
+}
+
+async fn run(schema: &HnSchema, query: &str) -> serde_json::Value {
+    let data: Value = schema.execute(query).await.into_result().unwrap().data;
+    serde_json::to_value(data).unwrap()
+}
+
+#[tokio::test]
+async fn activity_timeline_counts_comments_per_bucket() {
+    let schema = thread("timeline");
+
+    let data = run(
+        &schema,
+        r#"{ item(id: 100) { ... on Story {
+            hourly: activityTimeline { start comments }
+            halfHourly: activityTimeline(bucket: "30m") { comments }
+        } } }"#,
+    )
+    .await;
+    assert_eq!(
+        data["item"]["hourly"],
+        json!([
+            { "start": 1000, "comments": 1 },
+            { "start": 4600, "comments": 2 },
+            { "start": 8200, "comments": 1 },
+        ])
+    );
+    let half_hourly = data["item"]["halfHourly"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|bucket| bucket["comments"].as_u64().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(half_hourly, [1, 0, 2, 0, 1]);
+
+    let response = schema
+        .execute(
+            r#"{ item(id: 100) { ... on Story { activityTimeline(bucket: "0s") { comments } } } }"#,
+        )
+        .await;
+    assert!(response.is_err());
+}
+
+#[tokio::test]
+async fn story_thread_is_complete_unless_a_limit_cuts_it() {
+    let schema = thread("story-thread");
+    let query = |args: &str| {
+        format!(
+            "{{ storyThread(id: 100{}) {{ loadedCount complete consistent }} }}",
+            args
+        )
+    };
+
+    // Limits that the whole tree fits within.
+    for args in ["", ", depth: 2", ", perLevelLimit: 3"] {
+        assert_eq!(
+            run(&schema, &query(args)).await["storyThread"],
+            json!({ "loadedCount": 4, "complete": true, "consistent": true }),
+            "{}",
+            args
+        );
+    }
+
+    // Limits that leave replies out.
+    assert_eq!(
+        run(&schema, &query(", depth: 1")).await["storyThread"],
+        json!({ "loadedCount": 3, "complete": false, "consistent": false })
+    );
+    assert_eq!(
+        run(&schema, &query(", perLevelLimit: 2")).await["storyThread"],
+        json!({ "loadedCount": 3, "complete": false, "consistent": false })
+    );
+}
@@ -0,0 +1,73 @@
+//! Tests for the field usage extension.
+
+use async_graphql::{EmptyMutation, EmptySubscription, Object, Request, Schema, SimpleObject};
+use hn_gql::usage::{FieldUsage, ANONYMOUS_OPERATION, MAX_OPERATIONS, OTHER_OPERATIONS};
+
+#[derive(SimpleObject)]
+struct Story {
+    title: String,
+    score: u32,
+}
+
+struct Query;
+
+#[Object]
+impl Query {
+    async fn stories(&self) -> Vec<Story> {
+        (0..3)
+            .map(|score| Story {
+                title: "Ask HN: The Arc Effect".to_string(),
+                score,
+            })
+            .collect()
+    }
+}
+
+#[tokio::test]
+async fn fields_count_once_per_request_and_operation() {
+    let usage = FieldUsage::new();
+    let schema = Schema::build(Query, EmptyMutation, EmptySubscription)
+        .extension(usage.clone())
+        .finish();
+
+    schema
+        .execute(Request::new("query Titles { stories { title } }").operation_name("Titles"))
+        .await;
+    schema.execute("{ stories { title score } }").await;
+
+    let report = usage.report();
+    assert_eq!(
+        report.keys().collect::<Vec<_>>(),
+        ["Query.stories", "Story.score", "Story.title"]
+    );
+    assert_eq!(report["Story.title"].total, 2);
+    assert_eq!(report["Story.title"].operations["Titles"], 1);
+    assert_eq!(report["Story.title"].operations[ANONYMOUS_OPERATION], 1);
+    assert_eq!(report["Story.score"].total, 1);
+    assert!(!report["Story.score"].operations.contains_key("Titles"));
+}
+
+#[tokio::test]
+async fn operation_names_past_the_limit_are_folded() {
+    let usage = FieldUsage::new();
+    let schema = Schema::build(Query, EmptyMutation, EmptySubscription)
+        .extension(usage.clone())
+        .finish();
+
+    for n in 0..MAX_OPERATIONS + 5 {
+        let name = format!("Op{}", n);
+        let query = format!("query {} {{ stories {{ title }} }}", name);
+        schema
+            .execute(Request::new(query).operation_name(name))
+            .await;
+    }
+    schema
+        .execute(Request::new("query Op0 { stories { title } }").operation_name("Op0"))
+        .await;
+
+    let operations = &usage.report()["Story.title"].operations;
+    assert_eq!(operations.len(), MAX_OPERATIONS + 1);
+    assert_eq!(operations["Op0"], 2);
+    assert_eq!(operations[OTHER_OPERATIONS], 5);
+    assert!(!operations.contains_key(&format!("Op{}", MAX_OPERATIONS)));
+}